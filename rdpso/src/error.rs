@@ -0,0 +1,83 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+/// Error enumerates the failures of the simulator's public API
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// InvalidConfig reports a config field outside of its valid range
+    InvalidConfig {
+        field: String,
+        reason: String,
+    },
+
+    /// IndexOutOfRange reports an access past the end of a collection
+    IndexOutOfRange {
+        idx: usize,
+        len: usize,
+    },
+
+    /// EmptyPopulation reports an attempt to build a swarm without particles
+    EmptyPopulation,
+
+    /// InvalidInterpolation reports breakpoints which don't define a piecewise function
+    InvalidInterpolation(String),
+
+    /// Parse reports malformed configs, snapshots or recordings
+    Parse(String),
+
+    /// Serialization reports a failure to serialize the simulation state
+    Serialization(String),
+
+    /// Io reports a failure to write a recording
+    Io(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn invalid_config(field: &str, reason: &str) -> Self {
+        Self::InvalidConfig {
+            field: field.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// check_index returns IndexOutOfRange unless idx < len
+    pub fn check_index(idx: usize, len: usize) -> Result<usize> {
+        if idx < len {
+            Ok(idx)
+        } else {
+            Err(Self::IndexOutOfRange { idx, len })
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidConfig { field, reason } => write!(f, "invalid {}: {}", field, reason),
+            Self::IndexOutOfRange { idx, len } => write!(f, "idx must be less than {}, got {}", len, idx),
+            Self::EmptyPopulation => write!(f, "population size must be > 0"),
+            Self::InvalidInterpolation(reason) => write!(f, "invalid interpolation: {}", reason),
+            Self::Parse(reason) => write!(f, "parse error: {}", reason),
+            Self::Serialization(reason) => write!(f, "serialization error: {}", reason),
+            Self::Io(reason) => write!(f, "io error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// errors are thrown as JS Error exceptions
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        JsError::new(&err.to_string()).into()
+    }
+}
+
+impl From<Error> for String {
+    fn from(err: Error) -> Self {
+        err.to_string()
+    }
+}
@@ -0,0 +1,375 @@
+//! experiment runs parameter sweeps, every combination of the swept values
+//! is simulated a number of times with different seeds and the results are aggregated
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::runner::{self, OutputFormat, StopCondition};
+use crate::{SimConfig, Simulator};
+
+/// SweepSpec describes an experiment, ie
+///
+/// ```toml
+/// config = "default.toml"
+/// repetitions = 10
+/// iterations = 500
+///
+/// [[parameters]]
+/// path = "params.w"
+/// values = [0.4, 0.6, 0.8]
+///
+/// [[parameters]]
+/// path = "params.c1"
+/// range = { start = 1.0, stop = 2.0, step = 0.5 }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepSpec {
+    /// config is the base SimConfig file, relative paths are resolved against the spec's directory
+    pub config: PathBuf,
+
+    /// repetitions is the amount of runs per combination,
+    /// the nth repetition runs with the base config's seed + n
+    pub repetitions: u64,
+
+    pub iterations: u64,
+
+    #[serde(default)]
+    pub target: Option<f64>,
+
+    #[serde(default)]
+    pub stagnation: Option<u64>,
+
+    /// parameters are the swept config fields, all their combinations are simulated
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+}
+
+/// Parameter is a swept config field, addressed by its dotted path, ie `swarm.size`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Parameter {
+    pub path: String,
+
+    #[serde(flatten)]
+    pub axis: Axis,
+}
+
+/// Axis lists the values taken by a swept parameter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Axis {
+    /// Values is an explicit list of values of any type
+    Values(Vec<Value>),
+
+    /// Range is an evenly spaced grid from start to stop, both included
+    Range {
+        start: f64,
+        stop: f64,
+        step: f64,
+    },
+}
+
+impl Axis {
+    pub fn values(&self) -> Result<Vec<Value>> {
+        match self {
+            Axis::Values(values) => Ok(values.clone()),
+            Axis::Range { start, stop, step } => {
+                if *step <= 0.0 || stop < start {
+                    return Err(Error::Parse(format!("invalid range {}..{} with step {}", start, stop, step)));
+                }
+                // tolerance avoids dropping the last value due to rounding,
+                // values are rounded so that 0.4 + 0.2 is written as 0.6
+                let count = ((stop - start) / step + 1e-9).floor() as usize + 1;
+                Ok((0..count)
+                    .map(|i| Value::from(((start + i as f64 * step) * 1e12).round() / 1e12))
+                    .collect())
+            },
+        }
+    }
+}
+
+impl SweepSpec {
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let value = runner::read_config_value(path)?;
+        let mut spec: Self = serde_json::from_value(value).map_err(|err| Error::Parse(format!("invalid sweep spec: {}", err)))?;
+        if spec.config.is_relative() {
+            if let Some(dir) = path.parent() {
+                spec.config = dir.join(&spec.config);
+            }
+        }
+        Ok(spec)
+    }
+
+    pub fn stop_condition(&self) -> StopCondition {
+        StopCondition {
+            max_iterations: self.iterations,
+            target_score: self.target,
+            stagnation: self.stagnation,
+        }
+    }
+
+    /// points returns the cartesian product of the parameters' values,
+    /// each point is a list of (path, value) assignments
+    pub fn points(&self) -> Result<Vec<Vec<(String, Value)>>> {
+        let mut points = vec![Vec::new()];
+        for parameter in self.parameters.iter() {
+            let values = parameter.axis.values()?;
+            points = points.into_iter()
+                .flat_map(|point| {
+                    values.iter().map(move |value| {
+                        let mut point = point.clone();
+                        point.push((parameter.path.clone(), value.clone()));
+                        point
+                    })
+                })
+                .collect();
+        }
+        Ok(points)
+    }
+}
+
+/// RunResult is the outcome of a single simulation
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RunResult {
+    pub seed: u64,
+    pub score: f64,
+    pub converged_at: u64,
+    pub collisions: usize,
+}
+
+/// Stats summarizes a sample
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Stats {
+    pub mean: f64,
+    pub median: f64,
+
+    /// std is the sample standard deviation, 0 for single element samples
+    pub std: f64,
+}
+
+impl Stats {
+    pub fn new(sample: &[f64]) -> Self {
+        if sample.is_empty() {
+            return Self { mean: f64::NAN, median: f64::NAN, std: f64::NAN };
+        }
+
+        let n = sample.len();
+        let mean = sample.iter().sum::<f64>() / n as f64;
+
+        let mut sorted = sample.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+
+        let std = if n > 1 {
+            (sample.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        Self { mean, median, std }
+    }
+}
+
+/// PointSummary aggregates the runs of a single parameter combination
+#[derive(Debug, Clone)]
+pub struct PointSummary {
+    pub assignments: Vec<(String, Value)>,
+    pub runs: Vec<RunResult>,
+    pub score: Stats,
+    pub converged_at: Stats,
+    pub collisions: Stats,
+}
+
+impl PointSummary {
+    pub fn new(assignments: Vec<(String, Value)>, runs: Vec<RunResult>) -> Self {
+        let scores: Vec<f64> = runs.iter().map(|run| run.score).collect();
+        let converged_at: Vec<f64> = runs.iter().map(|run| run.converged_at as f64).collect();
+        let collisions: Vec<f64> = runs.iter().map(|run| run.collisions as f64).collect();
+        Self {
+            score: Stats::new(&scores),
+            converged_at: Stats::new(&converged_at),
+            collisions: Stats::new(&collisions),
+            assignments,
+            runs,
+        }
+    }
+
+    /// fields flattens the summary into named columns
+    fn fields(&self) -> Vec<(String, Value)> {
+        let mut fields = self.assignments.clone();
+        fields.push(("runs".to_string(), Value::from(self.runs.len())));
+        for (name, stats) in [("score", self.score), ("converged_at", self.converged_at), ("collisions", self.collisions)] {
+            fields.push((format!("{}_mean", name), Value::from(stats.mean)));
+            fields.push((format!("{}_median", name), Value::from(stats.median)));
+            fields.push((format!("{}_std", name), Value::from(stats.std)));
+        }
+        fields
+    }
+}
+
+/// run_sweep simulates every combination of the spec, repetitions included, using up to threads workers.
+/// Summaries are returned in the same order as SweepSpec::points.
+pub fn run_sweep(spec: &SweepSpec, overrides: &[String], threads: usize) -> Result<Vec<PointSummary>> {
+    let mut base = runner::read_sim_config_value(&spec.config)?;
+    for assignment in overrides {
+        runner::apply_override(&mut base, assignment)?;
+    }
+
+    // every config is built upfront, so that invalid points are reported before anything runs
+    let points = spec.points()?;
+    let mut configs = Vec::with_capacity(points.len());
+    for point in points.iter() {
+        let mut value = base.clone();
+        for (path, assignment) in point.iter() {
+            runner::set_path(&mut value, path, assignment.clone())?;
+        }
+        let config: SimConfig = serde_json::from_value(value)
+            .map_err(|err| Error::Parse(format!("invalid config for {:?}: {}", point, err)))?;
+        config.validate().map_err(|err| match err {
+            Error::InvalidConfig { field, reason } => Error::InvalidConfig { field, reason: format!("{} at {:?}", reason, point) },
+            err => err,
+        })?;
+        configs.push(config);
+    }
+
+    let repetitions = spec.repetitions.max(1);
+    let jobs: Vec<(usize, SimConfig)> = configs.iter()
+        .enumerate()
+        .flat_map(|(idx, config)| {
+            (0..repetitions).map(move |rep| {
+                let mut config = config.clone();
+                config.seed = config.seed.wrapping_add(rep);
+                (idx, config)
+            })
+        })
+        .collect();
+
+    let stop = spec.stop_condition();
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<RunResult>>> = Mutex::new(vec![None; jobs.len()]);
+    let error: Mutex<Option<Error>> = Mutex::new(None);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                let job = next.fetch_add(1, Ordering::Relaxed);
+                if job >= jobs.len() || error.lock().unwrap().is_some() {
+                    break;
+                }
+
+                match run_single(jobs[job].1.clone(), &stop) {
+                    Ok(result) => results.lock().unwrap()[job] = Some(result),
+                    Err(err) => {
+                        error.lock().unwrap().get_or_insert(err);
+                    },
+                }
+            });
+        }
+    });
+
+    if let Some(err) = error.into_inner().unwrap() {
+        return Err(err);
+    }
+
+    let mut runs: Vec<Vec<RunResult>> = vec![Vec::new(); points.len()];
+    for ((idx, _), result) in jobs.iter().zip(results.into_inner().unwrap()) {
+        runs[*idx].extend(result);
+    }
+
+    Ok(points.into_iter()
+        .zip(runs)
+        .map(|(point, runs)| PointSummary::new(point, runs))
+        .collect())
+}
+
+fn run_single(config: SimConfig, stop: &StopCondition) -> Result<RunResult> {
+    let seed = config.seed;
+    let mut simulator = Simulator::new(config)?;
+    let summary = runner::run(&mut simulator, stop, |_| Ok(()))?;
+    Ok(RunResult {
+        seed,
+        score: summary.historic_best.score,
+        converged_at: summary.converged_at,
+        collisions: simulator.get_swarm().get_collision_count(),
+    })
+}
+
+/// write_summaries writes one CSV row or JSON object per parameter combination
+pub fn write_summaries(out: &mut dyn Write, summaries: &[PointSummary], format: OutputFormat) -> Result<()> {
+    for (i, summary) in summaries.iter().enumerate() {
+        let fields = summary.fields();
+        let line = match format {
+            OutputFormat::JsonLines => {
+                let object: serde_json::Map<String, Value> = fields.into_iter().collect();
+                Value::Object(object).to_string()
+            },
+            OutputFormat::Csv => {
+                if i == 0 {
+                    let header: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+                    writeln!(out, "{}", header.join(",")).map_err(|err| Error::Io(err.to_string()))?;
+                }
+                let values: Vec<String> = fields.iter().map(|(_, value)| value.to_string()).collect();
+                values.join(",")
+            },
+        };
+        writeln!(out, "{}", line).map_err(|err| Error::Io(err.to_string()))?;
+    }
+    out.flush().map_err(|err| Error::Io(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_cover_every_combination() {
+        let spec: SweepSpec = serde_json::from_value(serde_json::json!({
+            "config": "default.toml",
+            "repetitions": 3,
+            "iterations": 10,
+            "parameters": [
+                {"path": "params.w", "range": {"start": 0.5, "stop": 1.0, "step": 0.25}},
+                {"path": "ctx.goal", "values": ["Ackley", "Griewank"]},
+            ],
+        })).unwrap();
+
+        let points = spec.points().unwrap();
+        assert_eq!(points.len(), 6);
+        assert_eq!(points[0], vec![("params.w".to_string(), Value::from(0.5)), ("ctx.goal".to_string(), Value::from("Ackley"))]);
+        assert_eq!(points[5][0].1, Value::from(1.0));
+
+        let stats = Stats::new(&[1.0, 3.0, 2.0, 6.0]);
+        assert_eq!(stats.mean, 3.0);
+        assert_eq!(stats.median, 2.5);
+        assert!((stats.std - (14.0f64 / 3.0).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn invalid_points_are_rejected_before_running() {
+        let spec: SweepSpec = serde_json::from_value(serde_json::json!({
+            "config": "configs/default.toml",
+            "repetitions": 1,
+            "iterations": 10,
+            "parameters": [{"path": "swarm.size", "values": [4, 0]}],
+        })).unwrap();
+
+        match run_sweep(&spec, &[], 1) {
+            Err(Error::InvalidConfig { field, reason }) => {
+                assert_eq!(field, "swarm.size");
+                assert!(reason.contains("swarm.size") && reason.contains('0'), "{}", reason);
+            },
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! benchmark implements standard 2D test functions for optimization,
+//! as catalogued by Jamil & Yang, "A literature survey of benchmark functions for global optimization problems".
+//! Every function is a minimization problem.
+
+use std::f64::consts;
+
+use crate::wasm_bindgen;
+use crate::space::{Boundary, Vector};
+use super::{Benchmark, Performance};
+
+/// GoalInfo describes a built-in benchmark: its canonical search domain,
+/// its global optima and its landscape properties
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct GoalInfo {
+    pub benchmark: Benchmark,
+
+    /// domain is the canonical search domain
+    pub domain: Boundary,
+
+    /// optimum is the global minimum value
+    pub optimum: f64,
+
+    /// separable goals can be optimized one coordinate at a time
+    pub separable: bool,
+
+    /// multimodal goals have local optima besides the global ones
+    pub multimodal: bool,
+
+    name: &'static str,
+
+    /// optima lists the (x, z) coordinates of every global optimum
+    optima: &'static [[f64; 2]],
+}
+
+#[wasm_bindgen]
+impl GoalInfo {
+
+    pub fn new(benchmark: Benchmark) -> Self {
+        let square = |half: f64| Boundary::new(-half, half, -half, half);
+        let (name, domain, optimum, optima, separable, multimodal): (_, _, _, &'static [[f64; 2]], _, _) = match benchmark {
+            Benchmark::Ackley => ("Ackley", square(32.768), 0.0, &[[0.0, 0.0]], false, true),
+            Benchmark::Griewank => ("Griewank", square(600.0), 0.0, &[[0.0, 0.0]], false, true),
+            Benchmark::Sphere => ("Sphere", square(5.12), 0.0, &[[0.0, 0.0]], true, false),
+            Benchmark::Rastrigin => ("Rastrigin", square(5.12), 0.0, &[[0.0, 0.0]], true, true),
+            Benchmark::Rosenbrock => ("Rosenbrock", Boundary::new(-5.0, 10.0, -5.0, 10.0), 0.0, &[[1.0, 1.0]], false, false),
+            Benchmark::Schwefel => ("Schwefel", square(500.0), 0.0, &[[420.968746, 420.968746]], true, true),
+            Benchmark::Himmelblau => ("Himmelblau", square(5.0), 0.0, &[
+                [3.0, 2.0],
+                [-2.805118, 3.131312],
+                [-3.779310, -3.283186],
+                [3.584428, -1.848126],
+            ], false, true),
+            Benchmark::Michalewicz => ("Michalewicz", Boundary::new(0.0, consts::PI, 0.0, consts::PI), -1.801303, &[[2.202906, consts::FRAC_PI_2]], true, true),
+            Benchmark::Eggholder => ("Eggholder", square(512.0), -959.640663, &[[512.0, 404.231805]], false, true),
+            Benchmark::Levy => ("Levy", square(10.0), 0.0, &[[1.0, 1.0]], false, true),
+            Benchmark::StyblinskiTang => ("Styblinski-Tang", square(5.0), -78.332331, &[[-2.903534, -2.903534]], true, true),
+            Benchmark::Easom => ("Easom", square(100.0), -1.0, &[[consts::PI, consts::PI]], true, true),
+            Benchmark::ShekelFoxholes => ("Shekel foxholes", square(65.536), 0.998004, &[[-32.0, -32.0]], false, true),
+            Benchmark::SixHumpCamel => ("Six-hump camel", Boundary::new(-3.0, 3.0, -2.0, 2.0), -1.031628, &[
+                [0.089842, -0.712656],
+                [-0.089842, 0.712656],
+            ], false, true),
+        };
+
+        Self {
+            benchmark,
+            domain,
+            optimum,
+            separable,
+            multimodal,
+            name,
+            optima,
+        }
+    }
+
+    /// all describes every built-in benchmark, so that UIs can list them
+    pub fn all() -> Vec<GoalInfo> {
+        Benchmark::ALL.iter().map(|benchmark| Self::new(*benchmark)).collect()
+    }
+
+    pub fn get_name(&self) -> String {
+        self.name.to_string()
+    }
+
+    /// get_optima returns every global optimum, the score is the optimum value
+    /// and the position lies in the xz plane
+    pub fn get_optima(&self) -> Vec<Performance> {
+        self.optima.iter()
+            .map(|[x, z]| Performance::new(Vector::new(*x, 0.0, *z), self.optimum))
+            .collect()
+    }
+}
+
+pub fn ackley(x: f64, z: f64) -> f64 {
+    let square_sum = x * x + z * z;
+    let cosine_sum = libm::cos(consts::TAU * x) + libm::cos(consts::TAU * z);
+    -20.0 * libm::exp(-0.2 * (0.5 * square_sum).sqrt()) - libm::exp(cosine_sum / 2.0) + consts::E + 20.0
+}
+
+pub fn griewank(x: f64, z: f64) -> f64 {
+    let square_sum = x * x + z * z;
+    let cosine_prod = libm::cos(x) * libm::cos(z / consts::SQRT_2);
+    1.0 + square_sum / 4000.0 - cosine_prod
+}
+
+pub fn sphere(x: f64, z: f64) -> f64 {
+    x * x + z * z
+}
+
+pub fn rastrigin(x: f64, z: f64) -> f64 {
+    let term = |v: f64| v * v - 10.0 * libm::cos(consts::TAU * v);
+    20.0 + term(x) + term(z)
+}
+
+pub fn rosenbrock(x: f64, z: f64) -> f64 {
+    let a = z - x * x;
+    let b = 1.0 - x;
+    100.0 * a * a + b * b
+}
+
+pub fn schwefel(x: f64, z: f64) -> f64 {
+    let term = |v: f64| v * libm::sin(v.abs().sqrt());
+    418.9828872724338 * 2.0 - term(x) - term(z)
+}
+
+pub fn himmelblau(x: f64, z: f64) -> f64 {
+    let a = x * x + z - 11.0;
+    let b = x + z * z - 7.0;
+    a * a + b * b
+}
+
+/// michalewicz uses the usual steepness m = 10
+pub fn michalewicz(x: f64, z: f64) -> f64 {
+    let term = |i: f64, v: f64| libm::sin(v) * libm::pow(libm::sin(i * v * v / consts::PI), 20.0);
+    -term(1.0, x) - term(2.0, z)
+}
+
+pub fn eggholder(x: f64, z: f64) -> f64 {
+    let z = z + 47.0;
+    -z * libm::sin((x / 2.0 + z).abs().sqrt()) - x * libm::sin((x - z).abs().sqrt())
+}
+
+pub fn levy(x: f64, z: f64) -> f64 {
+    let w1 = 1.0 + (x - 1.0) / 4.0;
+    let w2 = 1.0 + (z - 1.0) / 4.0;
+    let sin_squared = |v: f64| libm::sin(v) * libm::sin(v);
+
+    sin_squared(consts::PI * w1)
+        + (w1 - 1.0) * (w1 - 1.0) * (1.0 + 10.0 * sin_squared(consts::PI * w1 + 1.0))
+        + (w2 - 1.0) * (w2 - 1.0) * (1.0 + sin_squared(consts::TAU * w2))
+}
+
+pub fn styblinski_tang(x: f64, z: f64) -> f64 {
+    let term = |v: f64| v * v * v * v - 16.0 * v * v + 5.0 * v;
+    (term(x) + term(z)) / 2.0
+}
+
+pub fn easom(x: f64, z: f64) -> f64 {
+    let dx = x - consts::PI;
+    let dz = z - consts::PI;
+    -libm::cos(x) * libm::cos(z) * libm::exp(-(dx * dx + dz * dz))
+}
+
+/// shekel_foxholes is De Jong's fifth function, 25 holes laid on a 5x5 grid
+pub fn shekel_foxholes(x: f64, z: f64) -> f64 {
+    const HOLES: [f64; 5] = [-32.0, -16.0, 0.0, 16.0, 32.0];
+
+    let mut sum = 0.002;
+    for (j, (a1, a2)) in HOLES.iter().flat_map(|a2| HOLES.iter().map(move |a1| (a1, a2))).enumerate() {
+        let dx = x - a1;
+        let dz = z - a2;
+        sum += 1.0 / ((j + 1) as f64 + dx * dx * dx * dx * dx * dx + dz * dz * dz * dz * dz * dz);
+    }
+    1.0 / sum
+}
+
+pub fn six_hump_camel(x: f64, z: f64) -> f64 {
+    let x2 = x * x;
+    let z2 = z * z;
+    (4.0 - 2.1 * x2 + x2 * x2 / 3.0) * x2 + x * z + (-4.0 + 4.0 * z2) * z2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optima_match_metadata() {
+        for info in GoalInfo::all() {
+            for optimum in info.get_optima() {
+                let position = optimum.position;
+                assert!(info.domain.contains(&position), "{} optimum outside of its domain", info.name);
+                let score = info.benchmark.evaluate(position.x, position.z);
+                assert!((score - info.optimum).abs() < 1e-3, "{} at its optimum scores {}", info.name, score);
+            }
+
+            // no point of a coarse grid beats the known optimum
+            let steps = 200;
+            let domain = info.domain;
+            for i in 0..=steps {
+                for j in 0..=steps {
+                    let x = domain.get_min_x() + (domain.get_max_x() - domain.get_min_x()) * i as f64 / steps as f64;
+                    let z = domain.get_min_z() + (domain.get_max_z() - domain.get_min_z()) * j as f64 / steps as f64;
+                    assert!(info.benchmark.evaluate(x, z) >= info.optimum - 1e-3, "{} scores below its optimum at ({}, {})", info.name, x, z);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,383 @@
+//! dynamic implements goals which change over time, t being the simulation's iteration
+
+use std::f64::consts;
+use std::sync::{Arc, Mutex};
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{Error, Result};
+use crate::space::{Boundary, Vector};
+use crate::utils::Rng;
+use super::{Goal, Objective, Performance};
+
+/// Switch replaces the goal from the given iteration on
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Switch {
+    pub at: u64,
+    pub goal: Goal,
+}
+
+/// DynamicGoal moves, rotates and rescales a goal over time.
+/// At iteration t the landscape is drifted by t * drift, rotated by t * rotation_speed
+/// around the canonical origin and scaled by 1 + height_amplitude * sin(2πt / height_period).
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct DynamicGoal {
+    pub base: Box<Goal>,
+
+    /// drift_x and drift_z are the canonical distance travelled by the landscape per iteration
+    #[serde(default)]
+    pub drift_x: f64,
+    #[serde(default)]
+    pub drift_z: f64,
+
+    /// rotation_speed is given in radians per iteration
+    #[serde(default)]
+    pub rotation_speed: f64,
+
+    #[serde(default)]
+    pub height_amplitude: f64,
+
+    /// height_period is given in iterations, the height is constant if it's 0
+    #[serde(default)]
+    pub height_period: f64,
+
+    /// switches replace the base goal at scheduled iterations, sorted by iteration
+    #[serde(default)]
+    pub switches: Vec<Switch>,
+}
+
+impl DynamicGoal {
+
+    /// validate checks that the switches are sorted and that the goals they hold are valid,
+    /// field is the config path of the dynamic goal
+    pub fn validate(&self, field: &str) -> Result<()> {
+        if self.switches.windows(2).any(|pair| pair[0].at > pair[1].at) {
+            return Err(Error::invalid_config(&format!("{}.switches", field), "must be sorted by iteration"));
+        }
+        self.base.validate(&format!("{}.base", field))?;
+        for (i, switch) in self.switches.iter().enumerate() {
+            switch.goal.validate(&format!("{}.switches[{}].goal", field, i))?;
+        }
+        Ok(())
+    }
+
+    /// active_goal returns the goal in place at iteration t
+    fn active_goal(&self, t: f64) -> &Goal {
+        self.switches.iter()
+            .take_while(|switch| switch.at as f64 <= t)
+            .last()
+            .map_or(&self.base, |switch| &switch.goal)
+    }
+
+    /// to_base maps canonical coordinates at time t to the coordinates of the undisturbed goal
+    fn to_base(&self, x: f64, z: f64, t: f64) -> (f64, f64) {
+        let (dx, dz) = (x - self.drift_x * t, z - self.drift_z * t);
+        let angle = self.rotation_speed * t;
+        let (sin, cos) = (libm::sin(angle), libm::cos(angle));
+        (cos * dx + sin * dz, cos * dz - sin * dx)
+    }
+
+    fn height_factor(&self, t: f64) -> f64 {
+        if self.height_period == 0.0 {
+            return 1.0;
+        }
+        1.0 + self.height_amplitude * libm::sin(consts::TAU * t / self.height_period)
+    }
+}
+
+impl Objective for DynamicGoal {
+    fn evaluate(&self, x: f64, z: f64) -> f64 {
+        self.evaluate_at(x, z, 0.0)
+    }
+
+    fn evaluate_at(&self, x: f64, z: f64, t: f64) -> f64 {
+        let (bx, bz) = self.to_base(x, z, t);
+        self.active_goal(t).evaluate_at(bx, bz, t) * self.height_factor(t)
+    }
+
+    fn domain(&self) -> Boundary {
+        self.base.domain()
+    }
+
+    /// optimum is the base goal's one at the start of the run
+    fn optimum(&self) -> Option<Performance> {
+        self.base.optimum()
+    }
+
+    fn name(&self) -> String {
+        format!("dynamic {}", self.base.name())
+    }
+
+    fn is_dynamic(&self) -> bool {
+        true
+    }
+}
+
+fn default_peaks() -> usize { 10 }
+fn default_change_frequency() -> u64 { 50 }
+fn default_height_severity() -> f64 { 7.0 }
+fn default_width_severity() -> f64 { 1.0 }
+fn default_shift_length() -> f64 { 1.0 }
+fn default_min_height() -> f64 { 30.0 }
+fn default_max_height() -> f64 { 70.0 }
+fn default_min_width() -> f64 { 1.0 }
+fn default_max_width() -> f64 { 12.0 }
+fn default_half_size() -> f64 { 50.0 }
+
+/// MovingPeaksSpec configures Branke's Moving Peaks benchmark, the defaults follow its scenario 2.
+/// The landscape is the upper envelope of cones, f(x) = max_i H_i - W_i * |x - X_i|,
+/// thus it's a maximization problem. Every change_frequency iterations the heights and widths
+/// take a gaussian step and the peaks shift by shift_length.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct MovingPeaksSpec {
+    #[serde(default = "default_peaks")]
+    pub peaks: usize,
+    #[serde(default = "default_change_frequency")]
+    pub change_frequency: u64,
+    #[serde(default = "default_height_severity")]
+    pub height_severity: f64,
+    #[serde(default = "default_width_severity")]
+    pub width_severity: f64,
+    #[serde(default = "default_shift_length")]
+    pub shift_length: f64,
+
+    /// correlation is λ, how much a shift follows the previous one, 0 makes shifts random
+    #[serde(default)]
+    pub correlation: f64,
+
+    #[serde(default = "default_min_height")]
+    pub min_height: f64,
+    #[serde(default = "default_max_height")]
+    pub max_height: f64,
+    #[serde(default = "default_min_width")]
+    pub min_width: f64,
+    #[serde(default = "default_max_width")]
+    pub max_width: f64,
+
+    /// half_size bounds the peaks to the square ±half_size
+    #[serde(default = "default_half_size")]
+    pub half_size: f64,
+
+    /// seed makes the peaks' movements independent from the simulation's randomness
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl MovingPeaksSpec {
+
+    /// validate checks the ranges the peaks are clamped to, field is the config path of the spec
+    pub fn validate(&self, field: &str) -> Result<()> {
+        let check = |valid: bool, name: &str, reason: &str| {
+            if valid { Ok(()) } else { Err(Error::invalid_config(&format!("{}.{}", field, name), reason)) }
+        };
+
+        check(self.peaks > 0, "peaks", "must be > 0")?;
+        check(self.half_size.is_finite() && self.half_size > 0.0, "half_size", "must be > 0")?;
+        for (name, value) in [
+            ("height_severity", self.height_severity),
+            ("width_severity", self.width_severity),
+            ("shift_length", self.shift_length),
+        ] {
+            check(value.is_finite() && value >= 0.0, name, "must be >= 0")?;
+        }
+        check((0.0..=1.0).contains(&self.correlation), "correlation", "must be in [0, 1]")?;
+        check(self.min_height.is_finite(), "min_height", "must be finite")?;
+        check(self.max_height.is_finite() && self.min_height <= self.max_height, "max_height", "must be >= min_height")?;
+        check(self.min_width.is_finite() && self.min_width >= 0.0, "min_width", "must be >= 0")?;
+        check(self.max_width.is_finite() && self.min_width <= self.max_width, "max_width", "must be >= min_width")?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Peak {
+    x: f64,
+    z: f64,
+    height: f64,
+    width: f64,
+
+    /// shift is the latest displacement of the peak
+    shift: (f64, f64),
+}
+
+/// Landscape holds the peaks of a single epoch, ie the iterations between two changes,
+/// and the rng which draws the following changes
+#[derive(Debug, Clone)]
+struct Landscape {
+    epoch: usize,
+    rng: Rng,
+    peaks: Arc<[Peak]>,
+}
+
+/// MovingPeaks is the Moving Peaks benchmark, see MovingPeaksSpec.
+/// Only the latest epoch is kept, the changes are drawn from the seed
+/// so earlier epochs are regenerated from the initial landscape when needed.
+#[derive(Debug, Clone)]
+pub struct MovingPeaks {
+    spec: MovingPeaksSpec,
+    initial: Landscape,
+    current: Arc<Mutex<Landscape>>,
+}
+
+impl MovingPeaks {
+
+    pub fn new(spec: MovingPeaksSpec) -> Self {
+        let mut rng = Rng::new(spec.seed);
+        let initial_height = (spec.min_height + spec.max_height) / 2.0;
+        let peaks = (0..spec.peaks)
+            .map(|_| Peak {
+                x: spec.half_size * (2.0 * rng.gen_random() - 1.0),
+                z: spec.half_size * (2.0 * rng.gen_random() - 1.0),
+                height: initial_height,
+                width: spec.min_width + (spec.max_width - spec.min_width) * rng.gen_random(),
+                shift: (0.0, 0.0),
+            })
+            .collect();
+
+        let initial = Landscape { epoch: 0, rng, peaks };
+        Self {
+            spec,
+            current: Arc::new(Mutex::new(initial.clone())),
+            initial,
+        }
+    }
+
+    pub fn get_spec(&self) -> &MovingPeaksSpec {
+        &self.spec
+    }
+
+    /// peaks_at returns the peaks in place at iteration t,
+    /// going back in time replays the changes from the initial landscape
+    fn peaks_at(&self, t: f64) -> Arc<[Peak]> {
+        let epoch = (t.max(0.0) as u64 / self.spec.change_frequency.max(1)) as usize;
+        if epoch == 0 {
+            return Arc::clone(&self.initial.peaks);
+        }
+
+        let mut current = self.current.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if current.epoch > epoch {
+            *current = self.initial.clone();
+        }
+        while current.epoch < epoch {
+            let Landscape { epoch, rng, peaks } = &mut *current;
+            *peaks = peaks.iter().map(|peak| self.change(peak, rng)).collect();
+            *epoch += 1;
+        }
+        Arc::clone(&current.peaks)
+    }
+
+    /// change moves a peak as defined by the benchmark
+    fn change(&self, peak: &Peak, rng: &mut Rng) -> Peak {
+        let spec = &self.spec;
+        let height = (peak.height + spec.height_severity * rng.gen_gaussian()).clamp(spec.min_height, spec.max_height);
+        let width = (peak.width + spec.width_severity * rng.gen_gaussian()).clamp(spec.min_width, spec.max_width);
+
+        // the shift mixes a random direction with the previous shift, then it's rescaled to shift_length
+        let angle = consts::TAU * rng.gen_random();
+        let (rx, rz) = (libm::cos(angle) * spec.shift_length, libm::sin(angle) * spec.shift_length);
+        let (mx, mz) = ((1.0 - spec.correlation) * rx + spec.correlation * peak.shift.0, (1.0 - spec.correlation) * rz + spec.correlation * peak.shift.1);
+        let norm = (mx * mx + mz * mz).sqrt();
+        let shift = if norm > 0.0 { (mx * spec.shift_length / norm, mz * spec.shift_length / norm) } else { (0.0, 0.0) };
+
+        // peaks bounce off the borders
+        let (mut x, mut z, mut shift) = (peak.x + shift.0, peak.z + shift.1, shift);
+        if x.abs() > spec.half_size {
+            x = x.signum() * 2.0 * spec.half_size - x;
+            shift.0 = -shift.0;
+        }
+        if z.abs() > spec.half_size {
+            z = z.signum() * 2.0 * spec.half_size - z;
+            shift.1 = -shift.1;
+        }
+
+        Peak { x, z, height, width, shift }
+    }
+}
+
+impl PartialEq for MovingPeaks {
+    fn eq(&self, other: &Self) -> bool {
+        self.spec == other.spec
+    }
+}
+
+impl Objective for MovingPeaks {
+    fn evaluate(&self, x: f64, z: f64) -> f64 {
+        self.evaluate_at(x, z, 0.0)
+    }
+
+    fn evaluate_at(&self, x: f64, z: f64, t: f64) -> f64 {
+        self.peaks_at(t).iter()
+            .map(|peak| peak.height - peak.width * ((x - peak.x).powi(2) + (z - peak.z).powi(2)).sqrt())
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    fn domain(&self) -> Boundary {
+        Boundary::new(-self.spec.half_size, self.spec.half_size, -self.spec.half_size, self.spec.half_size)
+    }
+
+    /// optimum is the tallest peak at the start of the run
+    fn optimum(&self) -> Option<Performance> {
+        self.peaks_at(0.0).iter()
+            .max_by(|a, b| a.height.total_cmp(&b.height))
+            .map(|peak| Performance::new(Vector::new(peak.x, 0.0, peak.z), peak.height))
+    }
+
+    fn name(&self) -> String {
+        "Moving peaks".to_string()
+    }
+
+    fn is_dynamic(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::goal::Benchmark;
+
+    #[test]
+    fn landscapes_change_reproducibly() {
+        let spec: MovingPeaksSpec = serde_json::from_str(r#"{"change_frequency": 10, "seed": 3}"#).unwrap();
+        let peaks = MovingPeaks::new(spec.clone());
+        let twin = MovingPeaks::new(spec.clone());
+
+        // evaluation order doesn't matter, the landscape only changes every 10 iterations
+        let late = peaks.evaluate_at(5.0, 5.0, 95.0);
+        assert_eq!(twin.evaluate_at(5.0, 5.0, 9.0), peaks.evaluate_at(5.0, 5.0, 0.0));
+        assert_eq!(twin.evaluate_at(5.0, 5.0, 95.0), late);
+        assert_ne!(peaks.evaluate_at(5.0, 5.0, 10.0), peaks.evaluate_at(5.0, 5.0, 0.0));
+
+        // only the latest epoch is kept, earlier ones are replayed from the seed
+        let middle = peaks.evaluate_at(5.0, 5.0, 50.0);
+        assert_eq!(peaks.current.lock().unwrap().epoch, 5);
+        assert_eq!(peaks.evaluate_at(5.0, 5.0, 95.0), late);
+        assert_eq!(peaks.evaluate_at(5.0, 5.0, 50.0), middle);
+        assert_eq!(MovingPeaks::new(spec.clone()).evaluate_at(5.0, 5.0, 50.0), middle);
+
+        let drifting = DynamicGoal {
+            base: Box::new(Goal::Benchmark(Benchmark::Sphere)),
+            drift_x: 0.5,
+            drift_z: 0.0,
+            rotation_speed: 0.0,
+            height_amplitude: 0.0,
+            height_period: 0.0,
+            switches: vec![Switch { at: 20, goal: Goal::Benchmark(Benchmark::Rastrigin) }],
+        };
+        assert_eq!(drifting.evaluate_at(5.0, 0.0, 10.0), 0.0);
+        assert_eq!(drifting.evaluate_at(10.0, 0.0, 20.0), Benchmark::Rastrigin.evaluate(0.0, 0.0));
+
+        // invalid specs would panic when clamping the peaks, they're rejected by validate
+        let unstable = MovingPeaksSpec { height_severity: f64::NAN, ..spec };
+        assert!(spec.validate("goal").is_ok());
+        assert!(matches!(unstable.validate("goal"), Err(Error::InvalidConfig { field, .. }) if field == "goal.height_severity"));
+        let mut shuffled = drifting.clone();
+        shuffled.switches.insert(0, Switch { at: 30, goal: Goal::MovingPeaks(MovingPeaks::new(unstable)) });
+        assert_eq!(shuffled.validate("goal"), Err(Error::invalid_config("goal.switches", "must be sorted by iteration")));
+        shuffled.switches.swap(0, 1);
+        assert!(matches!(shuffled.validate("goal"), Err(Error::InvalidConfig { field, .. }) if field == "goal.switches[1].goal.moving_peaks.height_severity"));
+    }
+}
@@ -0,0 +1,563 @@
+//! expression parses and evaluates user defined goal functions,
+//! ie `sin(x/10)*cos(z/10) + 0.01*(x^2+z^2)`.
+//!
+//! The variables are x and z, the canonical coordinates, and t, the iteration.
+//! Operators are + - * / % and ^, which is right associative and binds tighter than unary minus,
+//! so -x^2 is -(x^2). The constants pi and e and the functions listed in Function are available.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::f64::consts;
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{Error, Result};
+use crate::space::Boundary;
+use super::{Objective, Performance};
+
+/// MAX_NESTING bounds the nesting of parentheses, calls, signs and exponents, which the parser recurses through
+const MAX_NESTING: usize = 64;
+
+/// MAX_HEIGHT bounds the height of the expression tree, which the evaluation recurses through,
+/// long chains such as x+x+...+x grow it without nesting
+const MAX_HEIGHT: usize = 1024;
+
+/// Expression is a parsed goal function, it's serialized as its source
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { source, tokens, pos: 0, len: source.chars().count(), depth: 0 };
+        let (root, _) = parser.parse_expr()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.error_at(token.column, &format!("unexpected {}", token.kind)));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    pub fn get_source(&self) -> &str {
+        &self.source
+    }
+
+    /// evaluate_at evaluates the expression at canonical coordinates and time t
+    pub fn evaluate_at(&self, x: f64, z: f64, t: f64) -> f64 {
+        self.root.eval(&[x, z, t])
+    }
+}
+
+impl fmt::Debug for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Expression").field(&self.source).finish()
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = Error;
+
+    fn try_from(source: String) -> Result<Self> {
+        Self::parse(&source)
+    }
+}
+
+impl From<Expression> for String {
+    fn from(expression: Expression) -> Self {
+        expression.source
+    }
+}
+
+impl Objective for Expression {
+    fn evaluate(&self, x: f64, z: f64) -> f64 {
+        self.evaluate_at(x, z, 0.0)
+    }
+
+    fn evaluate_at(&self, x: f64, z: f64, t: f64) -> f64 {
+        Expression::evaluate_at(self, x, z, t)
+    }
+
+    /// domain is unbounded, expressions are defined wherever their functions are
+    fn domain(&self) -> Boundary {
+        Boundary::new(f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    fn optimum(&self) -> Option<Performance> {
+        None
+    }
+
+    fn name(&self) -> String {
+        self.source.clone()
+    }
+
+    /// is_dynamic reports whether the expression depends on the time t
+    fn is_dynamic(&self) -> bool {
+        self.root.uses_variable(2)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Ln,
+    Log10,
+    Sqrt,
+    Abs,
+    Floor,
+    Ceil,
+    Sign,
+    Atan2,
+    Min,
+    Max,
+    Pow,
+    Hypot,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        let function = match name {
+            "sin" => Self::Sin,
+            "cos" => Self::Cos,
+            "tan" => Self::Tan,
+            "asin" => Self::Asin,
+            "acos" => Self::Acos,
+            "atan" => Self::Atan,
+            "sinh" => Self::Sinh,
+            "cosh" => Self::Cosh,
+            "tanh" => Self::Tanh,
+            "exp" => Self::Exp,
+            "ln" | "log" => Self::Ln,
+            "log10" => Self::Log10,
+            "sqrt" => Self::Sqrt,
+            "abs" => Self::Abs,
+            "floor" => Self::Floor,
+            "ceil" => Self::Ceil,
+            "sign" => Self::Sign,
+            "atan2" => Self::Atan2,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "pow" => Self::Pow,
+            "hypot" => Self::Hypot,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Self::Atan2 | Self::Min | Self::Max | Self::Pow | Self::Hypot => 2,
+            _ => 1,
+        }
+    }
+
+    fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            Self::Sin => libm::sin(a),
+            Self::Cos => libm::cos(a),
+            Self::Tan => libm::tan(a),
+            Self::Asin => libm::asin(a),
+            Self::Acos => libm::acos(a),
+            Self::Atan => libm::atan(a),
+            Self::Sinh => libm::sinh(a),
+            Self::Cosh => libm::cosh(a),
+            Self::Tanh => libm::tanh(a),
+            Self::Exp => libm::exp(a),
+            Self::Ln => libm::log(a),
+            Self::Log10 => libm::log10(a),
+            Self::Sqrt => a.sqrt(),
+            Self::Abs => a.abs(),
+            Self::Floor => a.floor(),
+            Self::Ceil => a.ceil(),
+            Self::Sign => if a == 0.0 { 0.0 } else { a.signum() },
+            Self::Atan2 => libm::atan2(a, b),
+            Self::Min => a.min(b),
+            Self::Max => a.max(b),
+            Self::Pow => libm::pow(a, b),
+            Self::Hypot => libm::hypot(a, b),
+        }
+    }
+}
+
+/// Node is a node of the expression tree, variables are indexed as x, z, t
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f64),
+    Variable(usize),
+    Negate(Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+impl Node {
+
+    /// uses_variable reports whether the variable with the given index appears in the tree
+    fn uses_variable(&self, idx: usize) -> bool {
+        match self {
+            Self::Number(_) => false,
+            Self::Variable(variable) => *variable == idx,
+            Self::Negate(node) => node.uses_variable(idx),
+            Self::Binary(_, left, right) => left.uses_variable(idx) || right.uses_variable(idx),
+            Self::Call(_, args) => args.iter().any(|arg| arg.uses_variable(idx)),
+        }
+    }
+
+    fn eval(&self, vars: &[f64; 3]) -> f64 {
+        match self {
+            Self::Number(value) => *value,
+            Self::Variable(idx) => vars[*idx],
+            Self::Negate(node) => -node.eval(vars),
+            Self::Binary(op, left, right) => {
+                let (a, b) = (left.eval(vars), right.eval(vars));
+                match op {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a * b,
+                    Operator::Div => a / b,
+                    Operator::Rem => a % b,
+                    Operator::Pow => libm::pow(a, b),
+                }
+            },
+            Self::Call(function, args) => {
+                let a = args[0].eval(vars);
+                let b = args.get(1).map_or(0.0, |arg| arg.eval(vars));
+                function.apply(a, b)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    Ident(String),
+    Symbol(char),
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(value) => write!(f, "number {}", value),
+            Self::Ident(name) => write!(f, "`{}`", name),
+            Self::Symbol(symbol) => write!(f, "`{}`", symbol),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+
+    /// column is the 1 based position of the token's first character
+    column: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // scientific notation, ie 1e-3
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = text.parse()
+                .map_err(|_| expression_error(source, column, &format!("invalid number `{}`", text)))?;
+            tokens.push(Token { kind: TokenKind::Number(value), column });
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Ident(chars[start..i].iter().collect()), column });
+        } else if "+-*/%^(),".contains(c) {
+            tokens.push(Token { kind: TokenKind::Symbol(c), column });
+            i += 1;
+        } else {
+            return Err(expression_error(source, column, &format!("unexpected character `{}`", c)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn expression_error(source: &str, column: usize, reason: &str) -> Error {
+    Error::Parse(format!("invalid expression `{}` at column {}: {}", source, column, reason))
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+
+    /// len is the length of the source in characters, errors at the end point past it
+    len: usize,
+
+    /// depth counts the nested calls of parse_unary, which every recursion of the parser goes through
+    depth: usize,
+}
+
+impl Parser<'_> {
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn error_at(&self, column: usize, reason: &str) -> Error {
+        expression_error(self.source, column, reason)
+    }
+
+    /// column returns the column of the next token, past the end if there's none
+    fn column(&self) -> usize {
+        self.peek().map_or(self.len + 1, |token| token.column)
+    }
+
+    /// node pairs a node with its height, the height of its tallest child plus one,
+    /// trees taller than MAX_HEIGHT are rejected
+    fn node(&self, node: Node, children_height: usize, column: usize) -> Result<(Node, usize)> {
+        if children_height >= MAX_HEIGHT {
+            return Err(self.error_at(column, &format!("more than {} levels of operations", MAX_HEIGHT)));
+        }
+        Ok((node, children_height + 1))
+    }
+
+    /// eat consumes the next token if it is the given symbol
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek().map(|token| &token.kind) == Some(&TokenKind::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<()> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        Err(match self.peek() {
+            Some(token) => self.error_at(token.column, &format!("expected `{}`, found {}", symbol, token.kind)),
+            None => self.error_at(self.len + 1, &format!("expected `{}`, found the end of the expression", symbol)),
+        })
+    }
+
+    /// parse_expr parses sums and differences, along with the height of their tree
+    fn parse_expr(&mut self) -> Result<(Node, usize)> {
+        let (mut node, mut height) = self.parse_term()?;
+        loop {
+            let column = self.column();
+            let op = if self.eat('+') {
+                Operator::Add
+            } else if self.eat('-') {
+                Operator::Sub
+            } else {
+                return Ok((node, height));
+            };
+            let (right, right_height) = self.parse_term()?;
+            (node, height) = self.node(Node::Binary(op, Box::new(node), Box::new(right)), height.max(right_height), column)?;
+        }
+    }
+
+    /// parse_term parses products, quotients and remainders
+    fn parse_term(&mut self) -> Result<(Node, usize)> {
+        let (mut node, mut height) = self.parse_unary()?;
+        loop {
+            let column = self.column();
+            let op = if self.eat('*') {
+                Operator::Mul
+            } else if self.eat('/') {
+                Operator::Div
+            } else if self.eat('%') {
+                Operator::Rem
+            } else {
+                return Ok((node, height));
+            };
+            let (right, right_height) = self.parse_unary()?;
+            (node, height) = self.node(Node::Binary(op, Box::new(node), Box::new(right)), height.max(right_height), column)?;
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<(Node, usize)> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            return Err(self.error_at(self.column(), &format!("nested deeper than {} levels", MAX_NESTING)));
+        }
+
+        let column = self.column();
+
+        let parsed = if self.eat('-') {
+            let (node, height) = self.parse_unary()?;
+            self.node(Node::Negate(Box::new(node)), height, column)?
+        } else if self.eat('+') {
+            self.parse_unary()?
+        } else {
+            self.parse_power()?
+        };
+        self.depth -= 1;
+        Ok(parsed)
+    }
+
+    /// parse_power parses right associative powers, the exponent may be negated as in 2^-x
+    fn parse_power(&mut self) -> Result<(Node, usize)> {
+        let (base, base_height) = self.parse_atom()?;
+        let column = self.column();
+        if self.eat('^') {
+            let (exponent, exponent_height) = self.parse_unary()?;
+            return self.node(Node::Binary(Operator::Pow, Box::new(base), Box::new(exponent)), base_height.max(exponent_height), column);
+        }
+        Ok((base, base_height))
+    }
+
+    fn parse_atom(&mut self) -> Result<(Node, usize)> {
+        let token = match self.peek() {
+            Some(token) => token.clone(),
+            None => return Err(self.error_at(self.len + 1, "unexpected end of the expression")),
+        };
+        self.pos += 1;
+        let column = token.column;
+
+        match token.kind {
+            TokenKind::Number(value) => Ok((Node::Number(value), 1)),
+            TokenKind::Symbol('(') => {
+                let parsed = self.parse_expr()?;
+                self.expect(')')?;
+                Ok(parsed)
+            },
+            TokenKind::Ident(name) if self.eat('(') => {
+                let function = Function::from_name(&name)
+                    .ok_or_else(|| self.error_at(column, &format!("unknown function `{}`", name)))?;
+
+                let (first, mut height) = self.parse_expr()?;
+                let mut args = vec![first];
+                while self.eat(',') {
+                    let (arg, arg_height) = self.parse_expr()?;
+                    args.push(arg);
+                    height = height.max(arg_height);
+                }
+                self.expect(')')?;
+
+                if args.len() != function.arity() {
+                    return Err(self.error_at(column,
+                        &format!("`{}` takes {} argument(s), got {}", name, function.arity(), args.len())));
+                }
+                self.node(Node::Call(function, args), height, column)
+            },
+            TokenKind::Ident(name) => match name.as_str() {
+                "x" => Ok((Node::Variable(0), 1)),
+                "z" => Ok((Node::Variable(1), 1)),
+                "t" => Ok((Node::Variable(2), 1)),
+                "pi" => Ok((Node::Number(consts::PI), 1)),
+                "e" => Ok((Node::Number(consts::E), 1)),
+                _ => Err(self.error_at(column, &format!("unknown variable `{}`, expected x, z or t", name))),
+            },
+            kind => Err(self.error_at(column, &format!("unexpected {}", kind))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Simulator;
+    use crate::tests::config;
+
+    #[test]
+    fn expressions_follow_precedence() {
+        let expression = Expression::parse("sin(x/10)*cos(z/10) + 0.01*(x^2+z^2)").unwrap();
+        let (x, z) = (3.0, -4.0);
+        let expected = libm::sin(x / 10.0) * libm::cos(z / 10.0) + 0.01 * (x * x + z * z);
+        assert_eq!(expression.evaluate_at(x, z, 0.0), expected);
+
+        assert_eq!(Expression::parse("-2^2").unwrap().evaluate_at(0.0, 0.0, 0.0), -4.0);
+        assert_eq!(Expression::parse("2^3^2").unwrap().evaluate_at(0.0, 0.0, 0.0), 512.0);
+        assert_eq!(Expression::parse("max(x, t) - 1e-1").unwrap().evaluate_at(1.0, 0.0, 5.0), 4.9);
+
+        let err = Expression::parse("sin(x").unwrap_err();
+        assert_eq!(err, Error::Parse("invalid expression `sin(x` at column 6: expected `)`, found the end of the expression".to_string()));
+        assert!(Expression::parse("foo(x)").is_err());
+        assert!(Expression::parse("x y").is_err());
+
+        // deep nesting is rejected instead of overflowing the stack
+        let nested = format!("{}x{}", "(".repeat(200_000), ")".repeat(200_000));
+        assert!(matches!(Expression::parse(&nested), Err(Error::Parse(reason)) if reason.contains("at column 65: nested deeper than 64 levels")));
+        assert!(Expression::parse(&vec!["x"; 100_000].join("+")).is_err());
+        assert_eq!(Expression::parse(&vec!["x"; 1000].join("+")).unwrap().evaluate_at(1.0, 0.0, 0.0), 1000.0);
+
+        assert!(Expression::parse("sin(x - t)").unwrap().is_dynamic());
+        assert!(!Expression::parse("sin(x)").unwrap().is_dynamic());
+    }
+
+    #[test]
+    fn expression_goals_are_configurable() {
+        let mut simulator = Simulator::new(config(3)).unwrap();
+        let config = simulator.dump_config().unwrap();
+        assert!(config.contains("\"goal\": \"Ackley\""));
+
+        let broken = config.replace("\"Ackley\"", "{\"expression\": \"sin(x\"}");
+        let err = simulator.set_config(&broken).unwrap_err().to_string();
+        assert!(err.contains("invalid expression `sin(x` at column 6: expected `)`"), "{}", err);
+
+        simulator.set_config(&config.replace("\"Ackley\"", "{\"expression\": \"x^2 + z^2 + t\"}")).unwrap();
+        simulator.reset().unwrap();
+        simulator.step();
+        let particle = simulator.capture_frame().particles[0];
+        assert_eq!(particle.score, particle.position.x.powi(2) + particle.position.z.powi(2) + 1.0);
+
+        let restored = Simulator::restore_bytes(&simulator.snapshot_bytes().unwrap()).unwrap();
+        assert_eq!(restored.get_goal_name(), "x^2 + z^2 + t");
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{Error, Result};
+use crate::space::{Boundary, Grid, GridFormat, GridInterpolation, FillPolicy};
+use super::{Objective, Performance};
+
+fn default_cell_size() -> f64 {
+    1.0
+}
+
+/// GridSpec describes a measured scalar field, either stored in a file or given inline.
+/// Grid coordinates are canonical coordinates, the Ctx's transform places them on the terrain.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct GridSpec {
+    /// name labels the goal, it defaults to the path
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// path is the data file, relative paths are resolved against the working directory
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// format overrides the format guessed from the path's extension
+    #[serde(default)]
+    pub format: Option<GridFormat>,
+
+    /// values are the grid's rows, used when there is no path, null marks missing samples
+    #[serde(default)]
+    pub values: Option<Vec<Vec<Option<f64>>>>,
+
+    /// width is the row length of raw f32 files
+    #[serde(default)]
+    pub width: usize,
+
+    /// origin_x, origin_z and cell_size georeference the samples,
+    /// they are ignored by ESRI ASCII files which have their own
+    #[serde(default)]
+    pub origin_x: f64,
+    #[serde(default)]
+    pub origin_z: f64,
+    #[serde(default = "default_cell_size")]
+    pub cell_size: f64,
+
+    #[serde(default)]
+    pub interpolation: GridInterpolation,
+
+    /// fill sets the value of queries outside of the grid,
+    /// grids with NODATA samples need a Constant fill which replaces them
+    #[serde(default)]
+    pub fill: FillPolicy,
+}
+
+impl GridSpec {
+
+    /// load reads the grid from the spec's file or inline values
+    pub fn load(&self) -> Result<Grid> {
+        match (&self.path, &self.values) {
+            (Some(path), _) => Grid::read(Path::new(path), self.format, self.width, self.origin_x, self.origin_z, self.cell_size),
+            (None, Some(rows)) => {
+                let rows: Vec<Vec<f64>> = rows.iter()
+                    .map(|row| row.iter().map(|value| value.unwrap_or(f64::NAN)).collect())
+                    .collect();
+                Grid::from_rows(&rows, self.origin_x, self.origin_z, self.cell_size)
+            },
+            (None, None) => Err(Error::Parse("grid goals need either a path or values".to_string())),
+        }
+    }
+}
+
+/// GridGoal is a goal sampled from a measured field, ie a gas concentration map.
+/// It serializes as an inline spec holding the loaded samples, see to_inline_spec.
+#[derive(Debug, Clone)]
+pub struct GridGoal {
+    spec: GridSpec,
+    grid: Arc<Grid>,
+}
+
+impl GridGoal {
+
+    /// new loads the spec's grid and replaces its NODATA samples with the Constant fill,
+    /// a goal has to score every position so grids with NODATA and a Clamp fill are rejected
+    pub fn new(spec: GridSpec) -> Result<Self> {
+        let mut grid = spec.load()?;
+        if grid.get_values().iter().any(|value| value.is_nan()) {
+            let fallback = match spec.fill {
+                FillPolicy::Constant(fallback) => fallback,
+                FillPolicy::Clamp => return Err(Error::Parse(format!(
+                    "the grid {} has NODATA samples, they need a Constant fill", spec.path.as_deref().or(spec.name.as_deref()).unwrap_or("goal")
+                ))),
+            };
+            let values = grid.get_values().iter()
+                .map(|&value| if value.is_nan() { fallback } else { value })
+                .collect();
+            let [origin_x, origin_z] = grid.get_origin();
+            grid = Grid::new(grid.get_width(), grid.get_height(), values, origin_x, origin_z, grid.get_cell_size())?;
+        }
+        let grid = Arc::new(grid);
+        Ok(Self {
+            spec,
+            grid,
+        })
+    }
+
+    pub fn get_spec(&self) -> &GridSpec {
+        &self.spec
+    }
+
+    pub fn get_grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// to_inline_spec returns a spec holding the loaded samples and their georeferencing,
+    /// so that the goal can be rebuilt without reading its file again
+    pub fn to_inline_spec(&self) -> GridSpec {
+        let [origin_x, origin_z] = self.grid.get_origin();
+        let values = self.grid.get_values()
+            .chunks(self.grid.get_width())
+            .map(|row| row.iter().copied().map(Some).collect())
+            .collect();
+        GridSpec {
+            name: Some(self.name()),
+            path: None,
+            format: None,
+            values: Some(values),
+            width: 0,
+            origin_x,
+            origin_z,
+            cell_size: self.grid.get_cell_size(),
+            interpolation: self.spec.interpolation,
+            fill: self.spec.fill,
+        }
+    }
+}
+
+/// grid goals are equal when they sample the same field in the same way, wherever it was loaded from
+impl PartialEq for GridGoal {
+    fn eq(&self, other: &Self) -> bool {
+        self.grid == other.grid
+            && self.spec.interpolation == other.spec.interpolation
+            && self.spec.fill == other.spec.fill
+    }
+}
+
+impl Objective for GridGoal {
+    fn evaluate(&self, x: f64, z: f64) -> f64 {
+        self.grid.sample(x, z, self.spec.interpolation, self.spec.fill)
+    }
+
+    fn domain(&self) -> Boundary {
+        self.grid.get_boundary()
+    }
+
+    /// optimum is unknown, whether the field's minimum or maximum is sought depends on the strategy
+    fn optimum(&self) -> Option<Performance> {
+        None
+    }
+
+    fn name(&self) -> String {
+        self.spec.name.clone()
+            .or_else(|| self.spec.path.clone())
+            .unwrap_or_else(|| "grid".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::goal::Goal;
+
+    #[test]
+    fn grid_goals_load_from_configs() {
+        let path = std::env::temp_dir().join("rdpso_grid_goal.csv");
+        std::fs::write(&path, "0, 10\n20, 30\n").unwrap();
+
+        let inline: Goal = serde_json::from_str(r#"{"grid": {"values": [[0, 10], [20, 30]], "cell_size": 100, "fill": {"Constant": -1}}}"#).unwrap();
+        let file: Goal = serde_json::from_value(serde_json::json!({"grid": {"path": path, "cell_size": 100, "interpolation": "Bicubic"}})).unwrap();
+        for goal in [&inline, &file] {
+            assert_eq!(goal.evaluate(50.0, 50.0), 15.0);
+        }
+        assert_eq!(inline.evaluate(500.0, 0.0), -1.0);
+        assert_eq!(file.evaluate(500.0, 0.0), 10.0);
+
+        // serialized goals carry their samples, they are restored once the file is gone
+        let bytes = rmp_serde::to_vec(&file).unwrap();
+        let json = serde_json::to_string(&file).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        for restored in [rmp_serde::from_slice::<Goal>(&bytes).unwrap(), serde_json::from_str::<Goal>(&json).unwrap()] {
+            assert_eq!(restored, file);
+            assert_eq!(restored.name(), file.name());
+        }
+
+        // NODATA samples take the fill value, so that positions next to them still score
+        let nodata: Goal = serde_json::from_str(r#"{"grid": {"values": [[0, null], [20, 30]], "fill": {"Constant": -1}}}"#).unwrap();
+        let json = serde_json::to_string(&nodata).unwrap();
+        for goal in [nodata, serde_json::from_str::<Goal>(&json).unwrap()] {
+            assert_eq!(goal.evaluate(1.0, 0.0), -1.0);
+            assert_eq!(goal.evaluate(0.5, 0.0), -0.5);
+            assert!(goal.evaluate(0.9, 0.9).is_finite());
+        }
+        let clamped = serde_json::from_str::<Goal>(r#"{"grid": {"values": [[0, null], [20, 30]]}}"#).unwrap_err();
+        assert!(clamped.to_string().contains("NODATA"), "{}", clamped);
+
+        let missing = serde_json::from_str::<Goal>(r#"{"grid": {"path": "missing.asc"}}"#).unwrap_err();
+        assert!(missing.to_string().contains("missing.asc"), "{}", missing);
+    }
+}
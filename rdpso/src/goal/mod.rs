@@ -0,0 +1,479 @@
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use crate::wasm_bindgen;
+use crate::error::{Error, Result};
+use serde::{Serialize, Serializer, Deserialize};
+use serde::ser::SerializeMap;
+use serde::de::IntoDeserializer;
+use serde::de::value::StrDeserializer;
+
+use super::space::Boundary;
+use super::space::Vector;
+use super::space::Mapper;
+
+mod benchmark;
+pub use benchmark::GoalInfo;
+
+mod transform;
+pub use transform::GoalTransform;
+
+mod objective;
+pub use objective::Objective;
+
+mod expression;
+pub use expression::Expression;
+
+mod field;
+pub use field::{GridGoal, GridSpec};
+
+mod dynamic;
+pub use dynamic::{DynamicGoal, Switch, MovingPeaks, MovingPeaksSpec};
+
+mod pareto;
+pub use pareto::{Criterion, ParetoArchive, ParetoPoint, dominates};
+
+#[derive(Debug, PartialEq,Copy, Clone, PartialOrd)]
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
+pub struct Performance {
+    pub score: f64,
+    pub position: Vector,
+
+    /// violation measures how far the position is from satisfying the constraints, 0 if it's feasible
+    #[serde(default)]
+    pub violation: f64,
+}
+
+#[wasm_bindgen]
+impl Performance {
+    pub fn new(position: Vector, score: f64) -> Self {
+        Self::new_with_violation(position, score, 0.0)
+    }
+
+    pub fn new_with_violation(position: Vector, score: f64, violation: f64) -> Self {
+        Self {
+            position,
+            score,
+            violation,
+        }
+    }
+
+    pub fn is_feasible(&self) -> bool {
+        self.violation == 0.0
+    }
+
+    pub fn get_score(&self) -> f64{
+        self.score
+    }
+
+    pub fn get_position(&self) -> Vector {
+        self.position
+    }
+}
+
+#[derive(Debug, PartialEq,Copy, Clone)]
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
+pub enum Strategy {
+    Maximize = 0,
+    Minimize = 1,
+}
+
+impl Strategy {
+
+    /// pick_best chooses between two values, based on which one is more desireable
+    /// for the current strategy.
+    /// ie if Minimize pick the smallest, if maximize picks the biggest
+    pub fn pick_best(&self, left: f64, right: f64) -> f64 {
+        match self {
+            Self::Maximize => {
+                if left > right {
+                    left
+                } else {
+                    right
+                }
+            },
+            Self::Minimize => {
+                if left < right {
+                    left
+                } else {
+                    right
+                }
+            },
+        }
+    }
+
+    /// is_better reports whether left is strictly more desireable than right,
+    /// performances with different constraint violations are compared with Deb's feasibility rules
+    pub fn is_better(&self, left: &Performance, right: &Performance) -> bool {
+        if left.violation != right.violation {
+            return left.violation < right.violation;
+        }
+        match self {
+            Self::Maximize => left.get_score() > right.get_score(),
+            Self::Minimize => left.get_score() < right.get_score(),
+        }
+    }
+
+    /// pick_best_performance follows Deb's feasibility rules: a feasible performance beats an infeasible one,
+    /// infeasible ones are ranked by their violation and feasible ones by their score
+    pub fn pick_best_performance(&self, left: &Performance, right: &Performance) -> Performance {
+        if left.violation != right.violation {
+            return if left.violation < right.violation { *left } else { *right };
+        }
+        match self {
+            Self::Maximize => {
+                if left.get_score() > right.get_score() {
+                    *left
+                } else {
+                    *right
+                }
+            },
+            Self::Minimize => {
+                if left.get_score() < right.get_score() {
+                    *left
+                } else {
+                    *right
+                }
+            },
+        }
+    }
+}
+
+/// Goal represents the Swarm's goal function, the task to be accomplished.
+/// It's either a built-in benchmark, a user defined expression or a measured field.
+/// In configs benchmarks are given by name, ie "Ackley", expressions as `{ expression = "x^2 + z^2" }`
+/// and fields as `{ grid = { path = "gas.asc" } }`, see GridSpec.
+/// Goals changing over time are given as `{ dynamic = { base = "Sphere", drift_x = 0.01 } }`,
+/// see DynamicGoal, or `{ moving_peaks = { peaks = 10 } }`, see MovingPeaksSpec
+#[derive(Debug, Clone)]
+#[derive(Deserialize)]
+#[serde(try_from = "GoalSpec")]
+pub enum Goal {
+    Benchmark(Benchmark),
+    Expression(Expression),
+    Grid(GridGoal),
+    Dynamic(DynamicGoal),
+    MovingPeaks(MovingPeaks),
+
+    /// Custom holds an objective defined outside of the crate, see Ctx::with_objective.
+    /// Its code can't be written out, serializing it fails.
+    Custom(Arc<dyn Objective>),
+}
+
+/// custom goals are equal when they share the same objective
+impl PartialEq for Goal {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Benchmark(a), Self::Benchmark(b)) => a == b,
+            (Self::Expression(a), Self::Expression(b)) => a == b,
+            (Self::Grid(a), Self::Grid(b)) => a == b,
+            (Self::Dynamic(a), Self::Dynamic(b)) => a == b,
+            (Self::MovingPeaks(a), Self::MovingPeaks(b)) => a == b,
+            (Self::Custom(a), Self::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Serialize for Goal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        GoalSpec::try_from(self)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+/// GoalSpec is the serialized form of a Goal
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GoalSpec {
+    Benchmark(Benchmark),
+    Expression {
+        expression: String,
+    },
+    Grid {
+        grid: GridSpec,
+    },
+    Dynamic {
+        dynamic: DynamicGoal,
+    },
+    MovingPeaks {
+        moving_peaks: MovingPeaksSpec,
+    },
+
+    /// Unknown catches misspelled benchmark names, so that they are reported as such
+    Unknown(String),
+}
+
+impl Serialize for GoalSpec {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Self::Benchmark(benchmark) => benchmark.serialize(serializer),
+            // expressions are written as maps rather than structs, since binary formats
+            // encode structs as arrays, which the untagged deserialization doesn't match
+            Self::Expression { expression } => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("expression", expression)?;
+                map.end()
+            },
+            Self::Grid { grid } => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("grid", grid)?;
+                map.end()
+            },
+            Self::Dynamic { dynamic } => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("dynamic", dynamic)?;
+                map.end()
+            },
+            Self::MovingPeaks { moving_peaks } => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("moving_peaks", moving_peaks)?;
+                map.end()
+            },
+            Self::Unknown(name) => name.serialize(serializer),
+        }
+    }
+}
+
+impl TryFrom<GoalSpec> for Goal {
+    type Error = Error;
+
+    fn try_from(spec: GoalSpec) -> Result<Self> {
+        match spec {
+            GoalSpec::Benchmark(benchmark) => Ok(Self::Benchmark(benchmark)),
+            GoalSpec::Expression { expression } => Expression::parse(&expression).map(Self::Expression),
+            GoalSpec::Grid { grid } => GridGoal::new(grid).map(Self::Grid),
+            GoalSpec::Dynamic { dynamic } => Ok(Self::Dynamic(dynamic)),
+            GoalSpec::MovingPeaks { moving_peaks } => Ok(Self::MovingPeaks(MovingPeaks::new(moving_peaks))),
+            GoalSpec::Unknown(name) => {
+                let deserializer: StrDeserializer<'_, serde::de::value::Error> = name.as_str().into_deserializer();
+                let err = Benchmark::deserialize(deserializer).err().map_or(String::new(), |err| err.to_string());
+                Err(Error::Parse(format!("invalid goal: {}", err)))
+            },
+        }
+    }
+}
+
+impl TryFrom<&Goal> for GoalSpec {
+    type Error = Error;
+
+    fn try_from(goal: &Goal) -> Result<Self> {
+        Ok(match goal {
+            Goal::Benchmark(benchmark) => Self::Benchmark(*benchmark),
+            Goal::Expression(expression) => Self::Expression { expression: expression.clone().into() },
+            Goal::Grid(grid) => Self::Grid { grid: grid.to_inline_spec() },
+            Goal::Dynamic(dynamic) => Self::Dynamic { dynamic: dynamic.clone() },
+            Goal::MovingPeaks(peaks) => Self::MovingPeaks { moving_peaks: peaks.get_spec().clone() },
+            Goal::Custom(objective) => {
+                return Err(Error::Serialization(format!("the custom objective {} can't be serialized", objective.name())));
+            },
+        })
+    }
+}
+
+impl From<Benchmark> for Goal {
+    fn from(benchmark: Benchmark) -> Self {
+        Self::Benchmark(benchmark)
+    }
+}
+
+impl Goal {
+    /// validate checks the parameters of dynamic goals, which are only used once the landscape changes.
+    /// field is the config path of the goal, ie `ctx.goal`
+    pub fn validate(&self, field: &str) -> Result<()> {
+        match self {
+            Self::Dynamic(dynamic) => dynamic.validate(&format!("{}.dynamic", field)),
+            Self::MovingPeaks(peaks) => peaks.get_spec().validate(&format!("{}.moving_peaks", field)),
+            _ => Ok(()),
+        }
+    }
+
+    /// get_benchmark returns the built-in benchmark, if any
+    pub fn get_benchmark(&self) -> Option<Benchmark> {
+        match self {
+            Self::Benchmark(benchmark) => Some(*benchmark),
+            _ => None,
+        }
+    }
+}
+
+impl Objective for Goal {
+    fn evaluate(&self, x: f64, z: f64) -> f64 {
+        self.evaluate_at(x, z, 0.0)
+    }
+
+    fn evaluate_at(&self, x: f64, z: f64, t: f64) -> f64 {
+        match self {
+            Self::Benchmark(benchmark) => benchmark.evaluate(x, z),
+            Self::Expression(expression) => expression.evaluate_at(x, z, t),
+            Self::Grid(grid) => grid.evaluate(x, z),
+            Self::Dynamic(dynamic) => dynamic.evaluate_at(x, z, t),
+            Self::MovingPeaks(peaks) => peaks.evaluate_at(x, z, t),
+            Self::Custom(objective) => objective.evaluate_at(x, z, t),
+        }
+    }
+
+    fn domain(&self) -> Boundary {
+        match self {
+            Self::Benchmark(benchmark) => Objective::domain(benchmark),
+            Self::Expression(expression) => expression.domain(),
+            Self::Grid(grid) => grid.domain(),
+            Self::Dynamic(dynamic) => dynamic.domain(),
+            Self::MovingPeaks(peaks) => peaks.domain(),
+            Self::Custom(objective) => objective.domain(),
+        }
+    }
+
+    fn optimum(&self) -> Option<Performance> {
+        match self {
+            Self::Benchmark(benchmark) => benchmark.optimum(),
+            Self::Expression(expression) => expression.optimum(),
+            Self::Grid(grid) => grid.optimum(),
+            Self::Dynamic(dynamic) => dynamic.optimum(),
+            Self::MovingPeaks(peaks) => peaks.optimum(),
+            Self::Custom(objective) => objective.optimum(),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::Benchmark(benchmark) => benchmark.name(),
+            Self::Expression(expression) => expression.name(),
+            Self::Grid(grid) => grid.name(),
+            Self::Dynamic(dynamic) => dynamic.name(),
+            Self::MovingPeaks(peaks) => peaks.name(),
+            Self::Custom(objective) => objective.name(),
+        }
+    }
+
+    fn is_dynamic(&self) -> bool {
+        match self {
+            Self::Dynamic(dynamic) => dynamic.is_dynamic(),
+            Self::MovingPeaks(peaks) => peaks.is_dynamic(),
+            Self::Expression(expression) => expression.is_dynamic(),
+            Self::Custom(objective) => objective.is_dynamic(),
+            _ => false,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+/// Benchmark enumerates the built-in goals, standard 2D benchmarks whose metadata is given by GoalInfo
+pub enum Benchmark {
+    Ackley,
+    Griewank,
+    Sphere,
+    Rastrigin,
+    Rosenbrock,
+    Schwefel,
+    Himmelblau,
+    Michalewicz,
+    Eggholder,
+    Levy,
+    StyblinskiTang,
+    Easom,
+    ShekelFoxholes,
+    SixHumpCamel,
+}
+
+impl Benchmark {
+    /// ALL lists every benchmark, in declaration order
+    pub const ALL: [Benchmark; 14] = [
+        Self::Ackley,
+        Self::Griewank,
+        Self::Sphere,
+        Self::Rastrigin,
+        Self::Rosenbrock,
+        Self::Schwefel,
+        Self::Himmelblau,
+        Self::Michalewicz,
+        Self::Eggholder,
+        Self::Levy,
+        Self::StyblinskiTang,
+        Self::Easom,
+        Self::ShekelFoxholes,
+        Self::SixHumpCamel,
+    ];
+
+    /// evaluate produces the score of canonical coordinates
+    pub fn evaluate(&self, x: f64, z: f64) -> f64 {
+        match self {
+            Self::Ackley => benchmark::ackley(x, z),
+            Self::Griewank => benchmark::griewank(x, z),
+            Self::Sphere => benchmark::sphere(x, z),
+            Self::Rastrigin => benchmark::rastrigin(x, z),
+            Self::Rosenbrock => benchmark::rosenbrock(x, z),
+            Self::Schwefel => benchmark::schwefel(x, z),
+            Self::Himmelblau => benchmark::himmelblau(x, z),
+            Self::Michalewicz => benchmark::michalewicz(x, z),
+            Self::Eggholder => benchmark::eggholder(x, z),
+            Self::Levy => benchmark::levy(x, z),
+            Self::StyblinskiTang => benchmark::styblinski_tang(x, z),
+            Self::Easom => benchmark::easom(x, z),
+            Self::ShekelFoxholes => benchmark::shekel_foxholes(x, z),
+            Self::SixHumpCamel => benchmark::six_hump_camel(x, z),
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct GoalSurface {
+    objective: Arc<dyn Objective>,
+    transform: GoalTransform,
+
+    /// parametric_mapper maps parametric coordinates into the drawn area, along x and z respectively
+    parametric_mapper: [Mapper; 2],
+
+    /// t is the time at which time dependent goals are drawn
+    t: f64,
+}
+
+#[wasm_bindgen]
+impl GoalSurface{
+    /// new draws a benchmark over a square of side domain_size centered on the origin
+    pub fn new(benchmark: Benchmark, transform: GoalTransform, domain_size: usize,) -> Self {
+        let half_domain = (domain_size / 2) as f64;
+        let boundary = Boundary::new(-half_domain, half_domain, -half_domain, half_domain);
+        Self::from_objective(Arc::new(benchmark), transform, boundary)
+    }
+
+    pub fn set_time(&mut self, t: f64) {
+        self.t = t;
+    }
+
+    /// eval evaluates the goal function at world coordinates
+    pub fn eval(&self, x: f64, z: f64) -> f64 {
+        let (x, z) = self.transform.to_canonical(x, z);
+        self.objective.evaluate_at(x, z, self.t)
+    }
+
+    /// parametric_eval evaluates the goal function for parametric variables in the range [0,1]
+    pub fn parametric_eval(&self, x: f64, z: f64) -> Vector {
+        let x = self.parametric_mapper[0].map(x);
+        let z = self.parametric_mapper[1].map(z);
+        let y = self.eval(x, z);
+        Vector::new(x, y, z)
+    }
+}
+
+impl GoalSurface {
+    /// from_objective builds the surface of any objective drawn over the given world area,
+    /// see new for the built-in goals
+    pub fn from_objective(objective: Arc<dyn Objective>, transform: GoalTransform, boundary: Boundary) -> Self {
+        Self {
+             objective,
+             transform,
+             parametric_mapper: [
+                 Mapper::new_from_pair([0.0, 1.0], [boundary.get_min_x(), boundary.get_max_x()]),
+                 Mapper::new_from_pair([0.0, 1.0], [boundary.get_min_z(), boundary.get_max_z()]),
+             ],
+             t: 0.0,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,105 @@
+use std::fmt;
+
+use crate::space::Boundary;
+use super::{Benchmark, GoalInfo, Performance};
+
+/// Objective is a fitness function over the xz plane.
+///
+/// Objectives are evaluated on canonical coordinates, the Ctx's GoalTransform
+/// places their domain on the terrain.
+/// Downstream crates plug their own objectives in as `Goal::Custom`, ie through `Ctx::with_objective`.
+pub trait Objective: fmt::Debug + Send + Sync {
+    /// evaluate produces the score of canonical coordinates
+    fn evaluate(&self, x: f64, z: f64) -> f64;
+
+    /// evaluate_at produces the score of canonical coordinates at time t, the current iteration.
+    /// Objectives are static unless they override it.
+    fn evaluate_at(&self, x: f64, z: f64, _t: f64) -> f64 {
+        self.evaluate(x, z)
+    }
+
+    /// domain is the region where the objective is meaningful
+    fn domain(&self) -> Boundary;
+
+    /// optimum returns a global optimum in canonical coordinates, if it's known
+    fn optimum(&self) -> Option<Performance>;
+
+    fn name(&self) -> String;
+
+    /// is_dynamic reports whether the landscape changes over time
+    fn is_dynamic(&self) -> bool {
+        false
+    }
+}
+
+/// the built-in benchmarks have well known optima
+impl Objective for Benchmark {
+    fn evaluate(&self, x: f64, z: f64) -> f64 {
+        Benchmark::evaluate(self, x, z)
+    }
+
+    fn domain(&self) -> Boundary {
+        GoalInfo::new(*self).domain
+    }
+
+    fn optimum(&self) -> Option<Performance> {
+        GoalInfo::new(*self).get_optima().first().copied()
+    }
+
+    fn name(&self) -> String {
+        GoalInfo::new(*self).get_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::space::Vector;
+    use crate::goal::{Criterion, GoalTransform, Strategy};
+    use crate::pso::Ctx;
+
+    /// Bowl stands for an objective defined outside of the crate
+    #[derive(Debug)]
+    struct Bowl;
+
+    impl Objective for Bowl {
+        fn evaluate(&self, x: f64, z: f64) -> f64 {
+            (x - 1.0) * (x - 1.0) + z * z
+        }
+
+        fn domain(&self) -> Boundary {
+            Boundary::new(-4.0, 4.0, -4.0, 4.0)
+        }
+
+        fn optimum(&self) -> Option<Performance> {
+            Some(Performance::new(Vector::new(1.0, 0.0, 0.0), 0.0))
+        }
+
+        fn name(&self) -> String {
+            "Bowl".to_string()
+        }
+    }
+
+    #[test]
+    fn custom_objectives_replace_the_goal() {
+        let ctx = Ctx::with_objective(Arc::new(Bowl), Strategy::Minimize, GoalTransform::new(10.0, 5.0, 0.0, 0.0));
+        assert_eq!(ctx.evaluate(15.0, 0.0), 0.0);
+        assert_eq!(ctx.evaluate(5.0, 10.0), 2.0);
+        assert_eq!(ctx.get_optima()[0].position, Vector::new(15.0, 0.0, 0.0));
+        assert_eq!(ctx.get_objective().name(), "Bowl");
+
+        // the objective is the goal, it can't be written out
+        assert_eq!(ctx.goal.name(), "Bowl");
+        assert_eq!(ctx.get_benchmark(), None);
+        assert!(serde_json::to_string(&ctx).unwrap_err().to_string().contains("Bowl"));
+        assert!(rmp_serde::to_vec(&ctx).is_err());
+
+        // custom objectives may be secondary criteria as well
+        let mut multi = Ctx::new(Benchmark::Sphere, Strategy::Minimize);
+        multi.objectives.push(Criterion { goal: ctx.goal.clone(), strategy: Strategy::Minimize });
+        assert_eq!(multi.evaluate_objectives_at(1.0, 0.0, 0.0), vec![1.0, 0.0]);
+        assert!(serde_json::to_string(&multi).is_err());
+    }
+}
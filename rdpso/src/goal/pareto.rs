@@ -0,0 +1,214 @@
+//! pareto holds the building blocks of multi-objective optimization:
+//! dominance between score vectors and the external archive of non-dominated solutions
+
+use serde::{Serialize, Deserialize};
+
+use crate::wasm_bindgen;
+use crate::space::Vector;
+use crate::utils::Rng;
+use super::{Goal, Strategy};
+
+/// Criterion is an objective of a multi-objective problem, a goal and whether it's maximized or minimized
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Criterion {
+    pub goal: Goal,
+    pub strategy: Strategy,
+}
+
+/// dominates reports whether the scores a are no worse than b for every criterion
+/// and strictly better for at least one, strategies give the direction of each criterion
+pub fn dominates(strategies: &[Strategy], a: &[f64], b: &[f64]) -> bool {
+    let mut better = false;
+    for ((strategy, &a), &b) in strategies.iter().zip(a).zip(b) {
+        let (a_better, b_better) = match strategy {
+            Strategy::Maximize => (a > b, b > a),
+            Strategy::Minimize => (a < b, b < a),
+        };
+        if b_better {
+            return false;
+        }
+        better |= a_better;
+    }
+    better
+}
+
+/// ParetoPoint is a solution of the front, a position with the score of every criterion
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ParetoPoint {
+    pub position: Vector,
+    pub scores: Vec<f64>,
+}
+
+impl ParetoPoint {
+    pub fn new(position: Vector, scores: Vec<f64>) -> Self {
+        Self {
+            position,
+            scores,
+        }
+    }
+}
+
+/// ParetoArchive keeps the non-dominated feasible solutions found so far, as in MOPSO.
+/// Once full, the most crowded solutions are pruned, so that the archive spreads along the front.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ParetoArchive {
+    capacity: usize,
+    strategies: Vec<Strategy>,
+    members: Vec<ParetoPoint>,
+}
+
+impl ParetoArchive {
+    pub fn new(capacity: usize, strategies: Vec<Strategy>) -> Self {
+        Self {
+            capacity,
+            strategies,
+            members: Vec::new(),
+        }
+    }
+
+    /// insert adds a solution unless it's dominated by, or equal to, a member.
+    /// Members it dominates are removed. Returns whether the solution is part of the archive.
+    pub fn insert(&mut self, point: ParetoPoint) -> bool {
+        let strategies = &self.strategies;
+        if self.members.iter().any(|member| member.scores == point.scores || dominates(strategies, &member.scores, &point.scores)) {
+            return false;
+        }
+        self.members.retain(|member| !dominates(strategies, &point.scores, &member.scores));
+        self.members.push(point);
+
+        let inserted = self.members.len() - 1;
+        let mut kept = true;
+        while self.members.len() > self.capacity.max(1) {
+            let distances = self.crowding_distances();
+            let crowded = (0..distances.len())
+                .min_by(|&a, &b| distances[a].total_cmp(&distances[b]))
+                .unwrap_or(0);
+            kept &= crowded != inserted;
+            self.members.remove(crowded);
+        }
+        kept
+    }
+
+    /// crowding_distances returns the crowding distance of every member, as defined by NSGA-II:
+    /// the normalized perimeter of the cuboid formed by its neighbours on the front.
+    /// The extremes of each criterion are infinitely far.
+    pub fn crowding_distances(&self) -> Vec<f64> {
+        let size = self.members.len();
+        let mut distances = vec![0.0; size];
+        for criterion in 0..self.strategies.len() {
+            let mut order: Vec<usize> = (0..size).collect();
+            order.sort_by(|&a, &b| self.members[a].scores[criterion].total_cmp(&self.members[b].scores[criterion]));
+            if size < 3 {
+                order.iter().for_each(|&i| distances[i] = f64::INFINITY);
+                continue;
+            }
+
+            let (min, max) = (self.members[order[0]].scores[criterion], self.members[order[size - 1]].scores[criterion]);
+            distances[order[0]] = f64::INFINITY;
+            distances[order[size - 1]] = f64::INFINITY;
+            if max <= min {
+                continue;
+            }
+            for window in order.windows(3) {
+                let gap = self.members[window[2]].scores[criterion] - self.members[window[0]].scores[criterion];
+                distances[window[1]] += gap / (max - min);
+            }
+        }
+        distances
+    }
+
+    /// select_leader picks a guide for a particle with a binary tournament,
+    /// the least crowded of two random members wins
+    pub fn select_leader(&self, rng: &mut Rng) -> Option<&ParetoPoint> {
+        let size = self.members.len();
+        if size == 0 {
+            return None;
+        }
+        let distances = self.crowding_distances();
+        let (a, b) = (rng.gen_random_range(0, size), rng.gen_random_range(0, size));
+        let winner = if distances[b] > distances[a] { b } else { a };
+        Some(&self.members[winner])
+    }
+
+    /// clear forgets every member, ie when the goals change
+    pub fn clear(&mut self) {
+        self.members.clear();
+    }
+
+    pub fn get_members(&self) -> &[ParetoPoint] {
+        &self.members
+    }
+
+    pub fn get_strategies(&self) -> &[Strategy] {
+        &self.strategies
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::config;
+    use crate::{goal, pso, Simulator};
+
+    #[test]
+    fn archives_keep_a_spread_front() {
+        let strategies = vec![Strategy::Maximize, Strategy::Minimize];
+        assert!(dominates(&strategies, &[2.0, 1.0], &[1.0, 1.0]));
+        assert!(!dominates(&strategies, &[2.0, 2.0], &[1.0, 1.0]));
+
+        let mut archive = ParetoArchive::new(4, strategies);
+        let point = |signal: f64, distance: f64| ParetoPoint::new(Vector::new(signal, 0.0, distance), vec![signal, distance]);
+        assert!(archive.insert(point(1.0, 1.0)));
+        assert!(!archive.insert(point(0.5, 2.0)));
+        assert!(archive.insert(point(2.0, 1.0)));
+        assert_eq!(archive.len(), 1);
+
+        for &(signal, distance) in [(3.0, 2.0), (4.0, 3.0), (4.1, 3.1), (6.0, 8.0)].iter() {
+            assert!(archive.insert(point(signal, distance)));
+        }
+
+        // the point in the densest region of the front is pruned
+        let front: Vec<f64> = archive.get_members().iter().map(|member| member.scores[0]).collect();
+        assert_eq!(front, vec![2.0, 3.0, 4.1, 6.0]);
+        assert_eq!(archive.crowding_distances()[0], f64::INFINITY);
+    }
+
+    #[test]
+    fn multi_objective_runs_expose_the_front() {
+        let mut config = config(4);
+        let signal = goal::Expression::parse("-hypot(x - 300, z - 300)").unwrap();
+        let distance = goal::Expression::parse("hypot(x - 10, z - 10)").unwrap();
+        config.ctx = pso::Ctx::from_goal(goal::Goal::Expression(signal), goal::Strategy::Maximize, goal::GoalTransform::identity());
+        config.ctx.objectives.push(goal::Criterion { goal: goal::Goal::Expression(distance), strategy: goal::Strategy::Minimize });
+        config.archive = pso::ArchiveConfig::new(20);
+        assert!(config.ctx.is_multi_objective());
+
+        let mut simulator = Simulator::new(config).unwrap();
+        for _ in 0..30 {
+            simulator.step();
+        }
+
+        let front = simulator.get_pareto_front();
+        let strategies = simulator.config.ctx.get_strategies();
+        assert!(!front.is_empty() && front.len() <= 20);
+        for point in &front {
+            assert_eq!(point.scores, simulator.config.ctx.evaluate_objectives_at(point.position.x, point.position.z, 0.0));
+            assert!(front.iter().all(|other| !goal::dominates(&strategies, &other.scores, &point.scores)));
+        }
+
+        let restored = Simulator::restore_bytes(&simulator.snapshot_bytes().unwrap()).unwrap();
+        assert_eq!(restored.get_pareto_front(), front);
+    }
+}
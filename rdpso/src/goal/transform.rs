@@ -0,0 +1,94 @@
+use serde::{Serialize, Deserialize};
+
+use crate::wasm_bindgen;
+use crate::space::Boundary;
+
+/// GoalTransform places a goal's canonical domain in world coordinates.
+/// A canonical point q lands on the world point offset + R(rotation) * scale * q,
+/// so scale is the amount of world units per canonical unit.
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct GoalTransform {
+    pub scale: f64,
+
+    /// offset_x and offset_z are the world position of the canonical origin
+    pub offset_x: f64,
+    pub offset_z: f64,
+
+    /// rotation is the counterclockwise rotation in the xz plane, in radians
+    pub rotation: f64,
+}
+
+impl Default for GoalTransform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+#[wasm_bindgen]
+impl GoalTransform {
+
+    pub fn new(scale: f64, offset_x: f64, offset_z: f64, rotation: f64) -> Self {
+        Self {
+            scale,
+            offset_x,
+            offset_z,
+            rotation,
+        }
+    }
+
+    /// identity evaluates goals on raw world coordinates
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// fit scales and centers the domain so that it covers the boundary,
+    /// the largest side of the domain spans the largest side of the boundary
+    pub fn fit(domain: &Boundary, boundary: &Boundary) -> Self {
+        let domain_side = f64::max(domain.get_max_x() - domain.get_min_x(), domain.get_max_z() - domain.get_min_z());
+        let boundary_side = f64::max(boundary.get_max_x() - boundary.get_min_x(), boundary.get_max_z() - boundary.get_min_z());
+        let scale = boundary_side / domain_side;
+
+        let domain_center = ((domain.get_min_x() + domain.get_max_x()) / 2.0, (domain.get_min_z() + domain.get_max_z()) / 2.0);
+        let boundary_center = ((boundary.get_min_x() + boundary.get_max_x()) / 2.0, (boundary.get_min_z() + boundary.get_max_z()) / 2.0);
+
+        Self::new(scale, boundary_center.0 - scale * domain_center.0, boundary_center.1 - scale * domain_center.1, 0.0)
+    }
+}
+
+impl GoalTransform {
+
+    /// to_canonical maps world coordinates into the goal's canonical domain
+    pub fn to_canonical(&self, x: f64, z: f64) -> (f64, f64) {
+        let (sin, cos) = (libm::sin(self.rotation), libm::cos(self.rotation));
+        let dx = x - self.offset_x;
+        let dz = z - self.offset_z;
+        ((cos * dx + sin * dz) / self.scale, (cos * dz - sin * dx) / self.scale)
+    }
+
+    /// to_world maps canonical coordinates into world coordinates, it is the inverse of to_canonical
+    pub fn to_world(&self, x: f64, z: f64) -> (f64, f64) {
+        let (sin, cos) = (libm::sin(self.rotation), libm::cos(self.rotation));
+        let x = x * self.scale;
+        let z = z * self.scale;
+        (self.offset_x + cos * x - sin * z, self.offset_z + sin * x + cos * z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_world_inverts_to_canonical() {
+        let transform = GoalTransform::new(15.0, 120.0, -40.0, 0.7);
+        let (x, z) = transform.to_world(3.0, -2.0);
+        let (cx, cz) = transform.to_canonical(x, z);
+        assert!((cx - 3.0).abs() < 1e-9 && (cz + 2.0).abs() < 1e-9);
+
+        let fitted = GoalTransform::fit(&Boundary::new(-5.0, 10.0, -5.0, 10.0), &Boundary::new(-500.0, 500.0, -500.0, 500.0));
+        let (x, z) = fitted.to_world(-5.0, 10.0);
+        assert!((x + 500.0).abs() < 1e-9 && (z - 500.0).abs() < 1e-9);
+    }
+}
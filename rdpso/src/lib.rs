@@ -1,10 +1,10 @@
 use wasm_bindgen::prelude::*;
 use serde::{Serialize, Deserialize};
 
-use goal::Goal;
+use error::{Error, Result};
 use goal::GoalSurface;
 
-mod utils;
+pub mod utils;
 pub mod terrain;
 
 pub mod pso;
@@ -12,62 +12,229 @@ pub mod goal;
 pub mod space;
 
 pub mod physics;
+pub mod error;
+pub mod recorder;
 
-#[derive(Debug, Clone, Copy)]
+#[cfg(not(target_family = "wasm"))]
+pub mod runner;
+#[cfg(not(target_family = "wasm"))]
+pub mod experiment;
+
+#[derive(Debug, Clone)]
 #[wasm_bindgen]
 #[derive(Serialize, Deserialize)]
 pub struct SimConfig {
     pub params: pso::ParameterSet,
+    #[wasm_bindgen(getter_with_clone)]
     pub terrain: terrain::Config,
     pub controller: pso::ControllerConfig,
     pub swarm: pso::SwarmConfig,
+
+    /// darwin sets the subswarm evolution, a single swarm without evolution by default
+    #[serde(default)]
+    pub darwin: pso::DarwinConfig,
+    #[wasm_bindgen(getter_with_clone)]
     pub ctx: pso::Ctx,
     pub particle: pso::ParticleConfig,
+
+    /// dynamic sets the responses to changes of time dependent goals
+    #[serde(default)]
+    pub dynamic: pso::DynamicConfig,
+
+    /// archive sets the Pareto archive of multi-objective problems, see Ctx::objectives
+    #[serde(default)]
+    pub archive: pso::ArchiveConfig,
+
+    /// constraints restrict the positions particles may take, on top of the terrain boundary
+    #[wasm_bindgen(skip)]
+    #[serde(default)]
+    pub constraints: pso::ConstraintConfig,
+
+    /// resampling sets how the noisy measurements of Ctx::noise are handled
+    #[serde(default)]
+    pub resampling: pso::ResamplingConfig,
+
+    /// seed initializes the simulator's random number generator,
+    /// identical configs produce identical runs
+    #[serde(default)]
+    pub seed: u64,
 }
 
 #[wasm_bindgen]
 impl SimConfig {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         params: pso::ParameterSet,
         terrain: terrain::Config,
         controller: pso::ControllerConfig,
         swarm: pso::SwarmConfig,
+        darwin: pso::DarwinConfig,
         ctx: pso::Ctx,
         particle: pso::ParticleConfig,
+        seed: u64,
     ) -> Self {
         Self {
             params,
             terrain,
             controller,
             swarm,
+            darwin,
             ctx,
             particle,
+            dynamic: pso::DynamicConfig::default(),
+            archive: pso::ArchiveConfig::default(),
+            constraints: pso::ConstraintConfig::default(),
+            resampling: pso::ResamplingConfig::default(),
+            seed,
         }
     }
+
+    /// validate checks that every field is within its valid range
+    pub fn validate(&self) -> Result<()> {
+        let check = |valid: bool, field: &str, reason: &str| {
+            if valid { Ok(()) } else { Err(Error::invalid_config(field, reason)) }
+        };
+
+        check(self.terrain.size > 0, "terrain.size", "must be > 0")?;
+        check(self.terrain.octave_count > 0, "terrain.octave_count", "must be > 0")?;
+        check(self.terrain.scaling_factor > 0.0, "terrain.scaling_factor", "must be > 0")?;
+        check(self.terrain.noise_octaves > 0, "terrain.noise_octaves", "must be > 0")?;
+        check(self.terrain.persistence.is_finite() && self.terrain.persistence > 0.0, "terrain.persistence", "must be > 0")?;
+        check(self.terrain.lacunarity.is_finite() && self.terrain.lacunarity >= 1.0, "terrain.lacunarity", "must be >= 1")?;
+        if let Some(heightmap) = self.terrain.heightmap.as_ref() {
+            heightmap.validate()?;
+        }
+        self.terrain.elevation_interpolator()?;
+        check(self.terrain.cache_resolution != 1, "terrain.cache_resolution", "must be 0 or >= 2")?;
+        check(self.terrain.cache_resolution <= terrain::MAX_CACHE_RESOLUTION, "terrain.cache_resolution",
+            &format!("must be <= {}", terrain::MAX_CACHE_RESOLUTION))?;
+
+        check(self.params.max_velocity > 0.0, "params.max_velocity", "must be > 0")?;
+        if self.params.velocity_mode == pso::VelocityMode::Fractional {
+            check(self.params.alpha > 0.0 && self.params.alpha <= 1.0, "params.alpha", "must be in (0, 1]")?;
+            check(self.params.fractional_order > 0, "params.fractional_order", "must be > 0")?;
+            check(self.params.fractional_order <= self.particle.position_log_size,
+                "params.fractional_order", "must be <= particle.position_log_size")?;
+        }
+
+        let sensor = self.controller.collision;
+        check(sensor.range > 0.0, "controller.collision.range", "must be > 0")?;
+        check(sensor.linear_step_size > 0.0 && sensor.linear_step_size < sensor.range,
+            "controller.collision.linear_step_size", "must be in (0, range)")?;
+        check(sensor.fov_angle > 0.0 && sensor.fov_angle <= std::f64::consts::TAU,
+            "controller.collision.fov_angle", "must be in (0, 2π]")?;
+        check(sensor.angular_step_size > 0.0 && sensor.angular_step_size < sensor.fov_angle,
+            "controller.collision.angular_step_size", "must be in (0, fov_angle)")?;
+
+        let swarm = self.swarm;
+        check(swarm.size > 0, "swarm.size", "must be > 0")?;
+        check(swarm.deploy_spread_radius >= 0.0, "swarm.deploy_spread_radius", "must be >= 0")?;
+        check(swarm.initial_swarm_velocity >= 0.0, "swarm.initial_swarm_velocity", "must be >= 0")?;
+        // the extent of heightmaps is only known once they're loaded, see build
+        check(self.terrain.heightmap.is_some() || self.terrain.get_boundary().contains(&swarm.deploy_position),
+            "swarm.deploy_position", "must be inside the terrain boundary")?;
+        if swarm.comm.enabled {
+            check(swarm.comm.range > 0.0, "swarm.comm.range", "must be > 0")?;
+            check(!swarm.comm.line_of_sight || (swarm.comm.los_step_size > 0.0 && swarm.comm.los_step_size < swarm.comm.range),
+                "swarm.comm.los_step_size", "must be in (0, range)")?;
+            check(swarm.comm.connectivity_threshold > 0.0 && swarm.comm.connectivity_threshold <= 1.0,
+                "swarm.comm.connectivity_threshold", "must be in (0, 1]")?;
+        }
+
+        if matches!(swarm.topology.kind, pso::TopologyKind::Ring | pso::TopologyKind::Random) {
+            check(swarm.topology.neighbours > 0 && swarm.topology.neighbours < swarm.size,
+                "swarm.topology.neighbours", "must be in (0, swarm.size)")?;
+        }
+
+        let transform = self.ctx.transform;
+        check(transform.scale.is_finite() && transform.scale > 0.0, "ctx.transform.scale", "must be > 0")?;
+        check(transform.offset_x.is_finite() && transform.offset_z.is_finite() && transform.rotation.is_finite(),
+            "ctx.transform", "offsets and rotation must be finite")?;
+
+        self.ctx.goal.validate("ctx.goal")?;
+        for (i, criterion) in self.ctx.objectives.iter().enumerate() {
+            criterion.goal.validate(&format!("ctx.objectives[{}].goal", i))?;
+        }
+
+        let noise = self.ctx.noise;
+        check(noise.sigma.is_finite() && noise.sigma >= 0.0, "ctx.noise.sigma", "must be >= 0")?;
+        check(self.resampling.samples > 0, "resampling.samples", "must be > 0")?;
+
+        check((0.0..=1.0).contains(&self.dynamic.randomize_fraction), "dynamic.randomize_fraction", "must be in [0, 1]")?;
+
+        check(self.archive.capacity > 0, "archive.capacity", "must be > 0")?;
+        self.constraints.validate()?;
+
+        let darwin = self.darwin;
+        check(darwin.initial_swarms > 0, "darwin.initial_swarms", "must be > 0")?;
+        check(darwin.min_swarms > 0, "darwin.min_swarms", "must be > 0")?;
+        check(darwin.max_swarms > 0, "darwin.max_swarms", "must be > 0")?;
+        check(darwin.min_swarms <= darwin.max_swarms, "darwin.min_swarms", "must be <= max_swarms")?;
+        check(darwin.max_population > 0, "darwin.max_population", "must be > 0")?;
+        check(darwin.min_population <= darwin.max_population, "darwin.min_population", "must be <= max_population")?;
+        // the default usize::MAX never deletes swarms, 0 would delete every swarm on its first update
+        check(darwin.stagnation_threshold > 0, "darwin.stagnation_threshold", "must be > 0")?;
+
+        Ok(())
+    }
 }
 
+/// Simulator owns the whole simulation state,
+/// it can be serialized with snapshot and brought back with restore
 #[derive(Debug)]
 #[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
 pub struct Simulator {
-    swarm: pso::Swarm,
+    swarm: pso::SubswarmManager,
     terrain: terrain::Terrain,
     config: SimConfig,
-    goal: Goal,
+
+    /// rng is the single source of randomness of the simulation
+    rng: utils::Rng,
+
+    /// playback replaces the live simulation by a recording, if set
+    playback: Option<recorder::Playback>,
 }
 
 #[wasm_bindgen]
 impl Simulator {
 
-    pub fn new(config: SimConfig) -> Self {
-        let terrain = terrain::Terrain::new(config.terrain);
-        let controller = pso::particle::ParticleController::new_from_config(config.ctx, config.controller);
-        let swarm = pso::Swarm::new(config.ctx,config.params, config.swarm, controller, config.particle, &terrain);
-        
-        Self {
-            swarm: swarm,
-            terrain: terrain,
-            config: config,
-            goal: config.ctx.goal,
+    /// new validates the config and deploys the swarm
+    pub fn new(config: SimConfig) -> Result<Simulator> {
+        utils::set_panic_hook();
+
+        config.validate()?;
+        Self::build(config)
+    }
+
+    /// from_recording builds a simulator which replays a JSON Lines or binary recording,
+    /// step advances through the recorded frames instead of simulating.
+    /// The terrain and the goal come from the recording, no file is read.
+    pub fn from_recording(data: &[u8]) -> Result<Simulator> {
+        let recording = recorder::Recording::parse(data)?;
+        let mut simulator = recording.initial;
+        simulator.playback = Some(recorder::Playback::new(recording.frames)?);
+        Ok(simulator)
+    }
+
+    pub fn is_playback(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// get_frame_count returns the amount of recorded frames, 0 outside of playback
+    pub fn get_frame_count(&self) -> usize {
+        self.playback.as_ref().map_or(0, |playback| playback.get_frame_count())
+    }
+
+    /// get_frame_idx returns the index of the frame being replayed
+    pub fn get_frame_idx(&self) -> usize {
+        self.playback.as_ref().map_or(0, |playback| playback.get_cursor())
+    }
+
+    /// seek jumps to the idxth recorded frame
+    pub fn seek(&mut self, idx: usize) {
+        if let Some(playback) = self.playback.as_mut() {
+            playback.seek(idx);
         }
     }
 
@@ -75,33 +242,85 @@ impl Simulator {
         self.config.params = params;
     }
 
-    pub fn reset(&mut self) {
-        let config = self.config;
-
-        let terrain = terrain::Terrain::new(config.terrain);
-        let controller = pso::particle::ParticleController::new_from_config(config.ctx, config.controller);
-        let swarm = pso::Swarm::new(config.ctx,config.params, config.swarm, controller, config.particle, &terrain);
-        self.swarm = swarm;
-        self.terrain = terrain;
-        self.goal = config.ctx.goal;
+    /// reset restarts the simulation with the current config, playbacks restart from the first frame
+    pub fn reset(&mut self) -> Result<()> {
+        if self.playback.is_some() {
+            self.seek(0);
+            return Ok(());
+        }
+        self.config.validate()?;
+        *self = Self::build(self.config.clone())?;
+        Ok(())
     }
 
     pub fn step(&mut self) {
-        self.swarm.update(&self.terrain)
+        match self.playback.as_mut() {
+            Some(playback) => playback.step(),
+            None => self.swarm.update(&self.terrain, &mut self.rng),
+        }
+    }
+
+    pub fn get_goal_name(&self) -> String {
+        self.config.ctx.get_goal_name()
     }
 
-    pub fn get_goal(&self) -> Goal{
-        self.goal
+    /// get_goal_info describes the current benchmark, ie its domain and known optima,
+    /// expression goals have no info
+    pub fn get_goal_info(&self) -> Option<goal::GoalInfo> {
+        self.config.ctx.get_benchmark().map(goal::GoalInfo::new)
     }
 
+    /// get_goal_surface returns the goal as it is at the current iteration
     pub fn get_goal_surface(&self) -> GoalSurface{
-        GoalSurface::new(self.goal, self.config.terrain.size)
+        let mut surface = GoalSurface::from_objective(self.config.ctx.get_objective(), self.config.ctx.transform, self.terrain.get_boundary());
+        let iteration = match self.playback.as_ref() {
+            Some(playback) => playback.get_frame().iteration,
+            None => self.swarm.get_iteration(),
+        };
+        surface.set_time(iteration as f64);
+        surface
+    }
+
+    /// is_goal_dynamic reports whether the goal changes over time, ie whether its surface needs redrawing
+    pub fn is_goal_dynamic(&self) -> bool {
+        self.config.ctx.get_objective().is_dynamic()
+    }
+
+    /// get_pareto_front returns the non-dominated solutions of multi-objective problems,
+    /// the scores of each one list the goal first and the objectives after it
+    pub fn get_pareto_front(&self) -> Vec<goal::ParetoPoint> {
+        self.swarm.get_pareto_front()
     }
 
-    pub fn get_config(&self) -> SimConfig {self.config}
+    /// get_goal_change_count returns how many changes of the goal the swarms detected
+    pub fn get_goal_change_count(&self) -> usize {
+        self.swarm.get_change_count()
+    }
+
+    pub fn get_config(&self) -> SimConfig {self.config.clone()}
     
-    pub fn dump_config(&self) -> String {
-        serde_json::to_string_pretty(&self.config).unwrap()
+    pub fn dump_config(&self) -> Result<String> {
+        serde_json::to_string_pretty(&self.config).map_err(|err| Error::Serialization(err.to_string()))
+    }
+
+    /// get_terrain_boundary returns the area of the generated terrain, which heightmaps define themselves
+    pub fn get_terrain_boundary(&self) -> space::Boundary {
+        self.terrain.get_boundary()
+    }
+
+    /// get_terrain_normal returns the terrain's upward unit normal at (x, z)
+    pub fn get_terrain_normal(&self, x: f64, z: f64) -> space::Vector {
+        self.terrain.get_normal(x, z)
+    }
+
+    /// get_terrain_slope returns the terrain's inclination at (x, z), in degrees
+    pub fn get_terrain_slope(&self, x: f64, z: f64) -> f64 {
+        self.terrain.get_slope(x, z)
+    }
+
+    /// get_terrain_curvature returns the terrain's mean curvature at (x, z), positive in hollows
+    pub fn get_terrain_curvature(&self, x: f64, z: f64) -> f64 {
+        self.terrain.get_curvature(x, z)
     }
 
     // for some reason rebuilding a terrain on JS from the raw pointer isn't working
@@ -112,12 +331,68 @@ impl Simulator {
 
     pub fn get_swarm_size(&self)  -> usize {self.config.swarm.size}
 
-    pub fn get_particle_position_by_idx(&self, id: usize) -> space::Vector {
-        self.swarm.get_position_by_idx(id)
+    pub fn get_particle_position_by_idx(&self, id: usize) -> Result<space::Vector> {
+        match self.playback.as_ref() {
+            Some(playback) => {
+                let particles = &playback.get_frame().particles;
+                Ok(particles[Error::check_index(id, particles.len())?].position)
+            },
+            None => self.swarm.get_position_by_idx(id),
+        }
+    }
+
+    pub fn get_subswarm_count(&self) -> usize {
+        self.swarm.get_swarm_count()
+    }
+
+    /// get_subswarm_members returns the ids of the particles in the idxth subswarm,
+    /// the ids can be used with get_particle_position_by_idx
+    pub fn get_subswarm_members(&self, idx: usize) -> Result<Vec<usize>> {
+        self.swarm.get_swarm_members(idx)
     }
 
-    pub fn set_config(&mut self, config: &str) -> Result<(), String> {
-            let conf: SimConfig = serde_json::from_str(&config).map_err(|err| format!("invalid config: {}",err.to_string()))?;
+    pub fn get_subswarm_best(&self, idx: usize) -> Result<goal::Performance> {
+        self.swarm.get_swarm_best(idx)
+    }
+
+    pub fn get_subswarm_historic_best(&self, idx: usize) -> Result<goal::Performance> {
+        self.swarm.get_swarm_historic_best(idx)
+    }
+
+    /// get_subswarm_links returns the communication graph of the idxth subswarm
+    /// as a flat list of particle id pairs, ie [a0, b0, a1, b1, ...]
+    pub fn get_subswarm_links(&self, idx: usize) -> Result<Vec<usize>> {
+        self.swarm.get_swarm_links(idx)
+    }
+
+    /// get_excluded_members returns the ids of the socially excluded particles
+    pub fn get_excluded_members(&self) -> Vec<usize> {
+        self.swarm.get_excluded_members()
+    }
+
+    /// snapshot serializes the whole simulation state as JSON,
+    /// restore resumes the simulation exactly where it was left
+    pub fn snapshot(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(|err| Error::Serialization(err.to_string()))
+    }
+
+    /// snapshot_bytes serializes the whole simulation state in a compact binary format
+    pub fn snapshot_bytes(&self) -> Result<Vec<u8>> {
+        rmp_serde::to_vec(self).map_err(|err| Error::Serialization(err.to_string()))
+    }
+
+    pub fn restore(snapshot: &str) -> Result<Simulator> {
+        serde_json::from_str(snapshot).map_err(|err| Error::Parse(format!("invalid snapshot: {}", err)))
+    }
+
+    pub fn restore_bytes(snapshot: &[u8]) -> Result<Simulator> {
+        rmp_serde::from_slice(snapshot).map_err(|err| Error::Parse(format!("invalid snapshot: {}", err)))
+    }
+
+    /// set_config parses and validates a JSON config, it's applied on the next reset
+    pub fn set_config(&mut self, config: &str) -> Result<()> {
+            let conf: SimConfig = serde_json::from_str(config).map_err(|err| Error::Parse(format!("invalid config: {}", err)))?;
+            conf.validate()?;
             self.config = conf;
             Ok(())
     }
@@ -125,7 +400,155 @@ impl Simulator {
 
 
 impl Simulator {
-    pub fn get_swarm(&self) -> &pso::Swarm{
+    /// build generates the terrain and deploys the swarm, config is assumed to be valid
+    fn build(config: SimConfig) -> Result<Self> {
+        let mut rng = utils::Rng::new(config.seed);
+        let terrain = terrain::Terrain::new(config.terrain.clone(), &mut rng)?;
+        if !terrain.get_boundary().contains(&config.swarm.deploy_position) {
+            return Err(Error::invalid_config("swarm.deploy_position", "must be inside the terrain boundary"));
+        }
+        let controller = pso::particle::ParticleController::new_from_config(config.ctx.clone(), config.controller)
+            .with_constraints(config.constraints.clone())
+            .with_resampling(config.resampling);
+        let swarm = pso::SubswarmManager::new(config.ctx.clone(), config.params, config.swarm, config.darwin, controller, config.particle, &terrain, &mut rng)?
+            .with_dynamic(config.dynamic)
+            .with_archive(config.archive);
+
+        Ok(Self {
+            swarm,
+            terrain,
+            config,
+            rng,
+            playback: None,
+        })
+    }
+
+    pub fn get_swarm(&self) -> &pso::SubswarmManager{
         &self.swarm
     }
-}
\ No newline at end of file
+
+    /// capture_frame returns the current state of every particle, the recorded one during playback
+    pub fn capture_frame(&self) -> recorder::Frame {
+        match self.playback.as_ref() {
+            Some(playback) => playback.get_frame().clone(),
+            None => recorder::Frame::capture(&self.swarm),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts;
+
+    /// config is the simulation fixture, it is shared with the tests of the other modules
+    pub(crate) fn config(seed: u64) -> SimConfig {
+        let sensor = pso::SensorConfig::new(10.0, 0.5, consts::PI / 6.0, consts::PI / 180.0);
+        let comm = pso::CommConfig::new(100.0, true, 1.0, 0.8);
+        let topology = pso::TopologyConfig::new(pso::TopologyKind::Random, 2);
+        SimConfig::new(
+            pso::ParameterSet::new(0.5, 2.0, 2.0, 0.5, 0.5, 10.0),
+            terrain::Config::new_from_size(1000),
+            pso::ControllerConfig::new(sensor),
+            pso::SwarmConfig::new(8, space::Vector::new(10.0, 300.0, 10.0), 10.0, 0.5, comm, topology),
+            pso::DarwinConfig::new(2, 1, 3, 2, 6, 5),
+            pso::Ctx::new(goal::Benchmark::Ackley, goal::Strategy::Minimize),
+            pso::ParticleConfig::new(5),
+            seed,
+        )
+    }
+
+    #[test]
+    fn same_seed_same_trajectories() {
+        let mut a = Simulator::new(config(7)).unwrap();
+        let mut b = Simulator::new(config(7)).unwrap();
+        for _ in 0..20 {
+            a.step();
+            b.step();
+        }
+        assert_eq!(a.get_swarm().get_positions(), b.get_swarm().get_positions());
+
+        b.reset().unwrap();
+        for _ in 0..20 {
+            b.step();
+        }
+        assert_eq!(a.get_swarm().get_positions(), b.get_swarm().get_positions());
+    }
+
+    #[test]
+    fn subswarm_links_follow_membership_changes() {
+        let mut simulator = Simulator::new(config(0)).unwrap();
+        for _ in 0..40 {
+            simulator.step();
+            for idx in 0..simulator.get_subswarm_count() {
+                let members = simulator.get_subswarm_members(idx).unwrap();
+                let links = simulator.get_subswarm_links(idx).unwrap();
+                assert!(links.iter().all(|id| members.contains(id)), "{:?} {:?}", links, members);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let mut outside = config(0);
+        outside.swarm.deploy_position = space::Vector::new(600.0, 300.0, 0.0);
+        let mut wide_fov = config(0);
+        wide_fov.controller.collision.fov_angle = 7.0;
+        let mut empty = config(0);
+        empty.swarm.size = 0;
+        let mut forgetful = config(0);
+        forgetful.params = forgetful.params.with_fractional_order(0.6, 6);
+        let mut shrinking = config(0);
+        shrinking.terrain.lacunarity = 0.5;
+        let mut narrow = config(0);
+        narrow.ctx.goal = serde_json::from_str(r#"{"moving_peaks": {"min_width": 5, "max_width": 1}}"#).unwrap();
+
+        assert!(config(0).validate().is_ok());
+        assert_eq!(Simulator::new(outside).unwrap_err(), Error::invalid_config("swarm.deploy_position", "must be inside the terrain boundary"));
+        assert!(matches!(wide_fov.validate(), Err(Error::InvalidConfig { field, .. }) if field == "controller.collision.fov_angle"));
+        assert!(empty.validate().is_err());
+        assert_eq!(forgetful.validate(), Err(Error::invalid_config("params.fractional_order", "must be <= particle.position_log_size")));
+        assert!(matches!(shrinking.validate(), Err(Error::InvalidConfig { field, .. }) if field == "terrain.lacunarity"));
+        assert_eq!(narrow.validate(), Err(Error::invalid_config("ctx.goal.moving_peaks.max_width", "must be >= min_width")));
+
+        let invalid_field = |edit: &dyn Fn(&mut SimConfig)| {
+            let mut invalid = config(0);
+            edit(&mut invalid);
+            match invalid.validate() {
+                Err(Error::InvalidConfig { field, .. }) => field,
+                other => panic!("{:?}", other),
+            }
+        };
+        assert_eq!(invalid_field(&|c| c.darwin.min_swarms = 0), "darwin.min_swarms");
+        assert_eq!(invalid_field(&|c| c.darwin.stagnation_threshold = 0), "darwin.stagnation_threshold");
+        assert_eq!(invalid_field(&|c| c.swarm.topology.neighbours = 0), "swarm.topology.neighbours");
+        assert_eq!(invalid_field(&|c| c.swarm.topology.neighbours = 8), "swarm.topology.neighbours");
+        assert_eq!(invalid_field(&|c| c.swarm.comm.connectivity_threshold = 0.0), "swarm.comm.connectivity_threshold");
+        assert_eq!(invalid_field(&|c| c.swarm.comm.connectivity_threshold = 1.5), "swarm.comm.connectivity_threshold");
+        assert_eq!(invalid_field(&|c| c.terrain.cache_resolution = terrain::MAX_CACHE_RESOLUTION + 1), "terrain.cache_resolution");
+
+        let simulator = Simulator::new(config(0)).unwrap();
+        assert_eq!(simulator.get_particle_position_by_idx(8), Err(Error::IndexOutOfRange { idx: 8, len: 8 }));
+        assert!(simulator.get_subswarm_members(5).is_err());
+    }
+
+    #[test]
+    fn restored_snapshots_resume_the_run() {
+        let mut simulator = Simulator::new(config(11)).unwrap();
+        for _ in 0..10 {
+            simulator.step();
+        }
+
+        let mut from_json = Simulator::restore(&simulator.snapshot().unwrap()).unwrap();
+        let mut from_bytes = Simulator::restore_bytes(&simulator.snapshot_bytes().unwrap()).unwrap();
+        for _ in 0..10 {
+            simulator.step();
+            from_json.step();
+            from_bytes.step();
+        }
+
+        assert_eq!(simulator.get_swarm().get_positions(), from_json.get_swarm().get_positions());
+        assert_eq!(simulator.get_swarm().get_positions(), from_bytes.get_swarm().get_positions());
+        assert_eq!(simulator.rng, from_bytes.rng);
+    }
+}
@@ -1,49 +1,181 @@
-use rdpso_sim::*;
-use std::f64::consts;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::thread;
 
+use clap::{Args, Parser, Subcommand};
+
+use rdpso_sim::experiment::{self, SweepSpec};
+use rdpso_sim::recorder::{Recorder, RecordingFormat};
+use rdpso_sim::runner::{self, IterationRecord, OutputFormat, RecordWriter, StopCondition};
+use rdpso_sim::Simulator;
+
+/// Headless RDPSO simulator
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a single simulation and write its per iteration output
+    Run(RunArgs),
+
+    /// Run a parameter sweep and write aggregated statistics per combination
+    Sweep(SweepArgs),
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Simulation config, either a .json or a .toml file
+    #[arg(short, long, required_unless_present = "resume")]
+    config: Option<PathBuf>,
+
+    /// Resume the simulation from a snapshot instead of starting from a config
+    #[arg(long, conflicts_with_all = ["config", "overrides"])]
+    resume: Option<PathBuf>,
+
+    /// Save a snapshot of the simulation once it stops, as JSON for .json files and as binary otherwise
+    #[arg(long)]
+    save: Option<PathBuf>,
+
+    /// Override a config field, ie --set swarm.size=20 --set ctx.goal=Griewank
+    #[arg(short = 's', long = "set", value_name = "PATH=VALUE")]
+    overrides: Vec<String>,
+
+    /// Maximum amount of iterations
+    #[arg(short = 'n', long, default_value_t = 1000)]
+    iterations: u64,
+
+    /// Stop once the historic best reaches this score
+    #[arg(long)]
+    target: Option<f64>,
+
+    /// Stop after this many iterations without improvement
+    #[arg(long)]
+    stagnation: Option<u64>,
+
+    /// Output file, defaults to stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format, csv or jsonl
+    #[arg(short, long, default_value = "csv")]
+    format: OutputFormat,
+
+    /// Record every particle's state, as binary for .bin files and as JSON Lines otherwise
+    #[arg(short, long)]
+    record: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct SweepArgs {
+    /// Sweep spec, either a .json or a .toml file
+    spec: PathBuf,
+
+    /// Override a field of the base config, applied before the swept values
+    #[arg(short = 's', long = "set", value_name = "PATH=VALUE")]
+    overrides: Vec<String>,
+
+    /// Amount of worker threads, defaults to the available parallelism
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Output file, defaults to stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format, csv or jsonl
+    #[arg(short, long, default_value = "csv")]
+    format: OutputFormat,
+}
 
 fn main() {
-    let config = SimConfig {
-        params: pso::ParameterSet{
-            w: 3.0,
-            c1: 2.0,
-            c2: 2.0,
-            c3: 0.0,
-            max_velocity: 10.0,
-        },
-        terrain: terrain::Config{
-            size: 1000,
-            octave_count: 4,
-            octave_delta: 0.01,
-            scaling_factor: 0.01,
-        },
-        controller: pso::ControllerConfig{
-            collision: pso::SensorConfig{
-                range: 10.0,
-                linear_step_size: 0.5,
-                fov_angle: consts::PI / 6.0,
-                angular_step_size: consts::PI / 180.0,
-            },
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Run(args) => run(args),
+        Command::Sweep(args) => sweep(args),
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn open_output(path: &Option<PathBuf>) -> Result<Box<dyn Write>, String> {
+    match path {
+        Some(path) => {
+            let file = File::create(path).map_err(|err| format!("can't create {}: {}", path.display(), err))?;
+            Ok(Box::new(io::BufWriter::new(file)))
         },
-        swarm: pso::SwarmConfig{
-            size: 5,
-            deploy_position: space::Vector::new(10.0, 10.0, 10.0),
-            deploy_spread_radius: 10.0,
-            initial_swarm_velocity: 0.5,
+        None => Ok(Box::new(io::BufWriter::new(io::stdout()))),
+    }
+}
+
+fn run(args: RunArgs) -> Result<(), String> {
+    let mut simulator = match (&args.config, &args.resume) {
+        (_, Some(snapshot)) => runner::load_snapshot(snapshot)?,
+        (Some(config), None) => Simulator::new(runner::load_config(config, &args.overrides)?)?,
+        (None, None) => return Err("either a config or a snapshot is required".to_string()),
+    };
+    let stop = StopCondition {
+        max_iterations: args.iterations,
+        target_score: args.target,
+        stagnation: args.stagnation,
+    };
+
+    let mut writer = RecordWriter::new(open_output(&args.output)?, args.format);
+
+    let mut recorder = match &args.record {
+        Some(path) => {
+            let mut recorder = Recorder::new(open_output(&args.record)?, RecordingFormat::from_path(path), &simulator)?;
+            recorder.record(&simulator.capture_frame())?;
+            Some(recorder)
         },
-        ctx: pso::Ctx::new(goal::Goal::Ackley, goal::Strategy::Minimize),
-        particle: pso::ParticleConfig {
-            position_log_size: 10,
-        }
+        None => None,
     };
 
-    let mut simulator = Simulator::new(config);
+    let summary = runner::run(&mut simulator, &stop, |simulator| {
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(&simulator.capture_frame())?;
+        }
+        writer.write(&IterationRecord::new(simulator))
+    })?;
+    writer.flush()?;
+    if let Some(recorder) = recorder.as_mut() {
+        recorder.flush()?;
+    }
+    if let Some(path) = &args.save {
+        runner::save_snapshot(&simulator, path)?;
+    }
 
-    for i in 0..10 {
-        simulator.step();
-        for position in simulator.get_swarm().get_positions(){
-            println!("{}: {:?}", i, position)
+    eprintln!(
+        "stopped after {} iterations ({:?}), historic best {} at iteration {}",
+        summary.iterations, summary.reason, summary.historic_best.score, summary.converged_at,
+    );
+    let front = simulator.get_pareto_front();
+    if !front.is_empty() {
+        eprintln!("pareto front of {} solutions (x, z: scores)", front.len());
+        for point in front {
+            eprintln!("{}, {}: {:?}", point.position.x, point.position.z, point.scores);
         }
     }
+    Ok(())
+}
+
+fn sweep(args: SweepArgs) -> Result<(), String> {
+    let spec = SweepSpec::load(&args.spec)?;
+    let threads = args.threads
+        .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1);
+
+    let summaries = experiment::run_sweep(&spec, &args.overrides, threads)?;
+    experiment::write_summaries(&mut open_output(&args.output)?, &summaries, args.format)?;
 
+    eprintln!("ran {} combinations, {} repetitions each", summaries.len(), spec.repetitions.max(1));
+    Ok(())
 }
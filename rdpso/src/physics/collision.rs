@@ -1,13 +1,15 @@
 use crate::wasm_bindgen;
+use serde::{Serialize, Deserialize};
 
 use crate::space::Vector;
-use crate::terrain::Terrain;
+use crate::terrain::HeightField;
 
 /// LinearDetector sweeps a line to identify whether a collision happens
 /// A collision is said to happen is the projection of direction vector placed 
 /// on a position yields a y value higher than the original position
 #[derive(Debug, Clone, Copy)]
 #[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
 pub struct LinearDetector {
     /// range indicates how far ahead the detector will check
     range: f64,
@@ -22,8 +24,8 @@ impl LinearDetector {
 
     pub fn new(range: f64, step_size: f64) -> Self {
         Self {
-            range: range,
-            step_size: step_size,
+            range,
+            step_size,
             step_count: (range / step_size) as usize,
         }
     }
@@ -37,7 +39,7 @@ impl LinearDetector {
 
     /// gen_collision returns the position of a collision detected by the detector
     /// if no collision was foudn returns None.
-    pub fn get_collision(&self, position: Vector, direction: Vector, land: &Terrain) -> Option<Vector> {
+    pub fn get_collision(&self, position: Vector, direction: Vector, land: &dyn HeightField) -> Option<Vector> {
         let direction = direction.unit();
         let step = self.step_size * direction;
         let mut position = Vector::new(position.x, position.y, position.z);
@@ -52,8 +54,36 @@ impl LinearDetector {
         None
     }
 
-    pub fn has_collision(&self, position: Vector, direction: Vector, land: &Terrain) -> bool {
+    pub fn get_range(&self) -> f64 {
+        self.range
+    }
+
+    pub fn has_collision(&self, position: Vector, direction: Vector, land: &dyn HeightField) -> bool {
         self.get_collision(position, direction, land).is_some()
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::space::Boundary;
+    use crate::terrain::surface::{Plane, Ridge};
+
+    #[test]
+    fn detectors_hit_known_surfaces() {
+        let boundary = Boundary::new(-100.0, 100.0, -100.0, 100.0);
+        let detector = LinearDetector::new(20.0, 0.5);
+        let ground = Plane::new(0.0, boundary);
+
+        let hit = detector.get_collision(Vector::new(0.0, 10.0, 0.0), Vector::new(1.0, -1.0, 0.0), &ground).unwrap();
+        assert!((hit.x - 10.0).abs() < 0.5 && hit.y == 0.0, "{:?}", hit);
+        assert!(!detector.has_collision(Vector::new(0.0, 10.0, 0.0), Vector::unit_y(), &ground));
+
+        // a wall 15 units ahead is within range, one 25 units ahead isn't
+        let wall = |x: f64| Ridge::new(x, 0.0, std::f64::consts::FRAC_PI_2, 0.0, 100.0, 1.0, boundary);
+        let hit = detector.get_collision(Vector::new(0.0, 10.0, 0.0), Vector::unit_x(), &wall(15.0)).unwrap();
+        assert!(hit.x > 14.0 && hit.x < 15.0, "{:?}", hit);
+        assert!(!detector.has_collision(Vector::new(0.0, 10.0, 0.0), Vector::unit_x(), &wall(25.0)));
+    }
+}
@@ -0,0 +1,257 @@
+use serde::{Serialize, Deserialize};
+
+use crate::wasm_bindgen;
+use crate::error::{Error, Result};
+use crate::goal::{Expression, Strategy};
+use crate::space::{Pair, Vector};
+use crate::terrain::HeightField;
+
+/// Constraint restricts the positions particles may take, constraints are given in world coordinates.
+/// In configs they are written as `{ Expression = "x + z - 900" }`, `{ NoGo = [[0, 0], [100, 0], [50, 80]] }`
+/// or `{ MaxAltitude = 150 }`.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum Constraint {
+    /// Expression is satisfied where g(x, z) <= 0, g may depend on the iteration t as well
+    Expression(Expression),
+
+    /// NoGo forbids the inside of a polygon, given by its [x, z] vertices
+    NoGo(Vec<Pair>),
+
+    /// MaxAltitude bounds the height of the particles above the terrain
+    MaxAltitude(f64),
+}
+
+impl Constraint {
+
+    /// violation measures how far a position is from satisfying the constraint, 0 if it does
+    pub fn violation(&self, p: Vector, t: f64, terrain: &dyn HeightField) -> f64 {
+        match self {
+            Self::Expression(g) => g.evaluate_at(p.x, p.z, t).max(0.0),
+            Self::NoGo(polygon) if contains(polygon, p.x, p.z) => {
+                let nearest = nearest_edge_point(polygon, p.x, p.z);
+                libm::hypot(nearest[0] - p.x, nearest[1] - p.z)
+            },
+            Self::NoGo(_) => 0.0,
+            Self::MaxAltitude(max) => (p.y - terrain.get_height(p.x, p.z) - max).max(0.0),
+        }
+    }
+
+    /// repair moves a position onto the feasible region, or as close as it can get
+    pub fn repair(&self, p: Vector, t: f64, terrain: &dyn HeightField) -> Vector {
+        match self {
+            Self::Expression(g) => {
+                // Newton steps along the gradient of g towards the g = 0 boundary
+                let mut p = p;
+                for _ in 0..REPAIR_STEPS {
+                    let value = g.evaluate_at(p.x, p.z, t);
+                    if value <= 0.0 {
+                        break;
+                    }
+                    let dx = (g.evaluate_at(p.x + GRADIENT_STEP, p.z, t) - g.evaluate_at(p.x - GRADIENT_STEP, p.z, t)) / (2.0 * GRADIENT_STEP);
+                    let dz = (g.evaluate_at(p.x, p.z + GRADIENT_STEP, t) - g.evaluate_at(p.x, p.z - GRADIENT_STEP, t)) / (2.0 * GRADIENT_STEP);
+                    let norm = dx * dx + dz * dz;
+                    if !(norm > 0.0 && norm.is_finite()) {
+                        break;
+                    }
+                    let step = (value + REPAIR_MARGIN) / norm;
+                    p = Vector::new(p.x - step * dx, p.y, p.z - step * dz);
+                }
+                p
+            },
+            Self::NoGo(polygon) if contains(polygon, p.x, p.z) => {
+                // the nearest point of the border, pushed slightly outwards
+                let nearest = nearest_edge_point(polygon, p.x, p.z);
+                let (dx, dz) = (nearest[0] - p.x, nearest[1] - p.z);
+                let distance = libm::hypot(dx, dz).max(f64::EPSILON);
+                Vector::new(nearest[0] + REPAIR_MARGIN * dx / distance, p.y, nearest[1] + REPAIR_MARGIN * dz / distance)
+            },
+            Self::NoGo(_) => p,
+            Self::MaxAltitude(max) => Vector::new(p.x, p.y.min(terrain.get_height(p.x, p.z) + max), p.z),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::NoGo(polygon) if polygon.len() < 3 => Err(Error::invalid_config("constraints.rules", "no-go polygons need at least 3 vertices")),
+            Self::MaxAltitude(max) if !max.is_finite() || *max <= 0.0 => Err(Error::invalid_config("constraints.rules", "max altitude must be > 0")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// REPAIR_STEPS bounds the Newton steps of a single repair, REPAIR_PASSES the passes over every constraint
+const REPAIR_STEPS: usize = 10;
+const REPAIR_PASSES: usize = 3;
+const REPAIR_MARGIN: f64 = 1e-6;
+const GRADIENT_STEP: f64 = 1e-3;
+
+/// contains tests whether (x, z) is inside the polygon with the even-odd rule
+fn contains(polygon: &[Pair], x: f64, z: f64) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let ([xi, zi], [xj, zj]) = (polygon[i], polygon[j]);
+        if (zi > z) != (zj > z) && x < (xj - xi) * (z - zi) / (zj - zi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// nearest_edge_point returns the point of the polygon's border closest to (x, z)
+fn nearest_edge_point(polygon: &[Pair], x: f64, z: f64) -> Pair {
+    let mut nearest = polygon[0];
+    let mut best = f64::INFINITY;
+    for (i, &[ax, az]) in polygon.iter().enumerate() {
+        let [bx, bz] = polygon[(i + 1) % polygon.len()];
+        let (ex, ez) = (bx - ax, bz - az);
+        let length = ex * ex + ez * ez;
+        let s = if length > 0.0 { (((x - ax) * ex + (z - az) * ez) / length).clamp(0.0, 1.0) } else { 0.0 };
+        let point = [ax + s * ex, az + s * ez];
+        let distance = libm::hypot(point[0] - x, point[1] - z);
+        if distance < best {
+            best = distance;
+            nearest = point;
+        }
+    }
+    nearest
+}
+
+/// ConstraintHandling selects how particles deal with constraints
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+pub enum ConstraintHandling {
+    /// Penalty worsens the score of infeasible positions by penalty * violation
+    #[default]
+    Penalty = 0,
+
+    /// Feasibility compares performances with Deb's rules: feasible positions beat infeasible ones,
+    /// infeasible ones are ranked by their violation, feasible ones by their score
+    Feasibility = 1,
+
+    /// Repair projects infeasible positions onto the feasible region before scoring them,
+    /// positions which can't be repaired are ranked with Deb's rules
+    Repair = 2,
+}
+
+fn default_penalty() -> f64 {
+    1000.0
+}
+
+/// ConstraintConfig declares the constraints on the particles' positions and how they are handled
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ConstraintConfig {
+    #[serde(default)]
+    pub handling: ConstraintHandling,
+
+    /// penalty is the weight of the violation, only used by ConstraintHandling::Penalty
+    #[serde(default = "default_penalty")]
+    pub penalty: f64,
+
+    #[serde(default)]
+    pub rules: Vec<Constraint>,
+}
+
+impl Default for ConstraintConfig {
+    fn default() -> Self {
+        Self {
+            handling: ConstraintHandling::default(),
+            penalty: default_penalty(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl ConstraintConfig {
+
+    /// violation sums the violations of every constraint
+    pub fn violation(&self, p: Vector, t: f64, terrain: &dyn HeightField) -> f64 {
+        self.rules.iter().map(|rule| rule.violation(p, t, terrain)).sum()
+    }
+
+    /// repair applies the repair of every constraint in turn,
+    /// as repairing one may break another, a few passes are made
+    pub fn repair(&self, mut p: Vector, t: f64, terrain: &dyn HeightField) -> Vector {
+        for _ in 0..REPAIR_PASSES {
+            if self.violation(p, t, terrain) == 0.0 {
+                break;
+            }
+            p = self.rules.iter().fold(p, |p, rule| rule.repair(p, t, terrain));
+        }
+        p
+    }
+
+    /// penalize worsens a score according to the strategy, when penalties are the handling technique
+    pub fn penalize(&self, score: f64, violation: f64, strategy: Strategy) -> f64 {
+        match strategy {
+            Strategy::Maximize => score - self.penalty * violation,
+            Strategy::Minimize => score + self.penalty * violation,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.penalty >= 0.0 && self.penalty.is_finite()) {
+            return Err(Error::invalid_config("constraints.penalty", "must be >= 0"));
+        }
+        self.rules.iter().try_for_each(|rule| rule.validate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain;
+    use crate::utils::Rng;
+    use crate::tests::config;
+    use crate::{goal, pso, Simulator};
+
+    #[test]
+    fn repairs_reach_the_feasible_region() {
+        let terrain = terrain::Terrain::new(terrain::Config::new_from_size(1000), &mut Rng::new(0)).unwrap();
+        let config: ConstraintConfig = serde_json::from_str(r#"{
+            "handling": "Repair",
+            "rules": [{"Expression": "x + z - 600"}, {"NoGo": [[100, 100], [300, 100], [300, 300], [100, 300]]}, {"MaxAltitude": 50}]
+        }"#).unwrap();
+        config.validate().unwrap();
+
+        let inside = Vector::new(150.0, terrain.get_height(150.0, 280.0) + 80.0, 280.0);
+        assert!((config.violation(inside, 0.0, &terrain) - 50.0).abs() < 1e-9);
+
+        let repaired = config.repair(inside, 0.0, &terrain);
+        assert_eq!(config.violation(repaired, 0.0, &terrain), 0.0);
+        assert!((repaired.z - 300.0).abs() < 1e-3, "{:?}", repaired);
+
+        let beyond = config.repair(Vector::new(500.0, 0.0, 400.0), 0.0, &terrain);
+        assert!((beyond.x + beyond.z - 600.0).abs() < 1e-3 && beyond.x + beyond.z <= 600.0);
+    }
+
+    #[test]
+    fn constrained_runs_avoid_no_go_zones() {
+        let target = goal::Expression::parse("hypot(x - 100, z - 100)").unwrap();
+        for &handling in [pso::ConstraintHandling::Penalty, pso::ConstraintHandling::Feasibility, pso::ConstraintHandling::Repair].iter() {
+            let mut config = config(6);
+            config.ctx = pso::Ctx::from_goal(goal::Goal::Expression(target.clone()), goal::Strategy::Minimize, goal::GoalTransform::identity());
+            config.constraints.handling = handling;
+            config.constraints.rules.push(pso::Constraint::NoGo(vec![[50.0, 50.0], [150.0, 50.0], [150.0, 150.0], [50.0, 150.0]]));
+
+            let mut simulator = Simulator::new(config).unwrap();
+            for _ in 0..40 {
+                simulator.step();
+                if handling == pso::ConstraintHandling::Repair {
+                    assert!(simulator.get_swarm().get_particles().all(|p| p.get_violation() == 0.0));
+                }
+            }
+
+            let best = simulator.get_swarm().get_historic_best();
+            assert_eq!(simulator.config.constraints.violation(best.position, 40.0, &simulator.terrain), 0.0, "{:?}", handling);
+            assert!(best.is_feasible());
+        }
+    }
+}
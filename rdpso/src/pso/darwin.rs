@@ -0,0 +1,499 @@
+use crate::wasm_bindgen;
+use crate::error::{Error, Result};
+use serde::{Serialize, Deserialize};
+
+use crate::space::Vector;
+use crate::goal::{Performance, ParetoArchive, ParetoPoint};
+use super::ParameterSet;
+use super::Ctx;
+use super::particle::ParticleController;
+use super::particle::ParticleState;
+use crate::utils::Rng;
+use super::swarm::{Swarm, deploy};
+use super::{SwarmConfig, ParticleConfig, DarwinConfig, DynamicConfig, ArchiveConfig};
+use crate::terrain::HeightField;
+
+/// SubswarmManager implements the Darwinian evolution of the RDPSO.
+///
+/// Particles are split into subswarms which are rewarded when they improve,
+/// by recruiting a new member and possibly spawning a new subswarm, and punished when they stagnate,
+/// by losing their worst member or being deleted altogether.
+/// Particles removed from a swarm join the socially excluded group, which searches
+/// on its own and is the source of new members and new subswarms.
+#[derive(Debug)]
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
+pub struct SubswarmManager {
+    ctx: Ctx,
+    params: ParameterSet,
+    config: DarwinConfig,
+    controller: ParticleController,
+    swarm_config: SwarmConfig,
+    swarms: Vec<Swarm>,
+
+    /// excluded holds the socially excluded particles
+    excluded: Vec<ParticleState>,
+
+    best: Performance,
+    historic_best: Performance,
+
+    /// positions holds the position of every particle, indexed by the particle id
+    positions: Vec<Vector>,
+    iteration: u64,
+
+    /// dynamic sets the responses to changes of the goal
+    #[serde(default)]
+    dynamic: DynamicConfig,
+
+    /// changes counts the detected changes of the goal
+    #[serde(default)]
+    changes: usize,
+
+    /// archive holds the Pareto front of multi-objective problems, it's unused otherwise
+    #[serde(default)]
+    archive: ParetoArchive,
+
+    /// historic_samples counts the measurements averaged into the historic best's score
+    #[serde(default)]
+    historic_samples: usize,
+}
+
+#[wasm_bindgen]
+impl SubswarmManager {
+
+    /// evolve rewards the swarms that improved and punishes the stagnated ones
+    fn evolve(&mut self, terrain: &dyn HeightField, rng: &mut Rng) {
+        let min_population = self.config.min_population.max(1);
+        let mut deletable = self.swarms.len().saturating_sub(self.config.min_swarms.max(1));
+        let swarm_count = self.swarms.len() as f64;
+        let mut spawn_requests = 0;
+
+        let mut survivors = Vec::with_capacity(self.swarms.len());
+        for mut swarm in std::mem::take(&mut self.swarms) {
+            if swarm.has_improved() {
+                if swarm.get_population_size() < self.config.max_population {
+                    if let Some(particle) = self.take_best_excluded() {
+                        swarm.add_particle(particle, terrain);
+                    }
+                }
+
+                // spawning becomes less likely the more swarms there are
+                if rng.gen_random() < 1.0 / swarm_count {
+                    spawn_requests += 1;
+                }
+            } else if swarm.get_stagnation() > self.config.stagnation_threshold {
+                if swarm.get_population_size() > min_population {
+                    if let Some(particle) = swarm.exclude_worst(self.config.stagnation_threshold, terrain) {
+                        self.excluded.push(particle);
+                    }
+                } else if deletable > 0 {
+                    deletable -= 1;
+                    self.excluded.extend(swarm.disband());
+                    continue;
+                }
+            }
+            survivors.push(swarm);
+        }
+        self.swarms = survivors;
+
+        for _ in 0..spawn_requests {
+            if self.swarms.len() >= self.config.max_swarms || self.excluded.len() < min_population {
+                break;
+            }
+            let members: Vec<ParticleState> = (0..min_population).filter_map(|_| self.take_best_excluded()).collect();
+            // the members stay excluded if no swarm can be built out of them
+            match Swarm::from_population(self.ctx.clone(), self.params, self.controller.clone(), members.clone(), &self.swarm_config, terrain, rng) {
+                Ok(swarm) => self.swarms.push(swarm),
+                Err(_) => self.excluded.extend(members),
+            }
+        }
+    }
+
+    /// take_best_excluded removes the excluded particle with the best personal performance
+    fn take_best_excluded(&mut self) -> Option<ParticleState> {
+        if self.excluded.is_empty() {
+            return None;
+        }
+
+        let strategy = self.ctx.get_strategy();
+        let mut best = 0;
+        for (i, particle) in self.excluded.iter().enumerate().skip(1) {
+            if strategy.is_better(&particle.get_best_performance(), &self.excluded[best].get_best_performance()) {
+                best = i;
+            }
+        }
+        Some(self.excluded.swap_remove(best))
+    }
+
+    /// detect_change scores the historic best's position at the given iteration and the previous one,
+    /// the goal changed if they differ. Exact values are compared, so that measurement noise isn't mistaken for a change.
+    fn detect_change(&self, iteration: u64) -> bool {
+        let position = self.historic_best.position;
+        let t = iteration as f64;
+        self.ctx.evaluate_at(position.x, position.z, t) != self.ctx.evaluate_at(position.x, position.z, t - 1.0)
+    }
+
+    /// remeasure_bests measures every personal, swarm and global best again
+    fn remeasure_bests(&mut self, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) {
+        for swarm in self.swarms.iter_mut() {
+            swarm.remeasure_bests(iteration, terrain, rng);
+        }
+        for particle in self.excluded.iter_mut() {
+            self.controller.remeasure_best(particle, iteration, terrain, rng);
+        }
+        self.controller.remeasure(&mut self.historic_best, &mut self.historic_samples, iteration, terrain, rng);
+    }
+
+    /// respond_to_change applies the configured responses to every particle,
+    /// the bests are recomputed since they refer to the previous landscape
+    fn respond_to_change(&mut self, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) {
+        for swarm in self.swarms.iter_mut() {
+            swarm.respond_to_change(&self.dynamic, iteration, terrain, rng);
+        }
+        for particle in self.excluded.iter_mut() {
+            if self.dynamic.randomize_fraction > 0.0 && rng.gen_random() < self.dynamic.randomize_fraction {
+                self.controller.relocate(particle, iteration, terrain, rng);
+            } else {
+                self.controller.respond_to_change(particle, &self.dynamic, iteration, terrain, rng);
+            }
+        }
+
+        self.changes += 1;
+        self.archive.clear();
+        self.fill_archive();
+        self.historic_best = self.swarms[0].get_best();
+        self.historic_samples = self.controller.get_resampling().samples;
+        self.update_positions();
+        self.update_bests();
+    }
+
+    /// fill_archive offers the current position of every feasible particle to the Pareto archive
+    fn fill_archive(&mut self) {
+        if !self.ctx.is_multi_objective() {
+            return;
+        }
+        let points: Vec<ParetoPoint> = self.get_particles()
+            .filter(|p| p.get_violation() == 0.0)
+            .map(|p| p.get_pareto_point())
+            .collect();
+        for point in points {
+            self.archive.insert(point);
+        }
+    }
+
+    fn update_positions(&mut self) {
+        let positions: Vec<(usize, Vector)> = self.get_particles().map(|p| (p.get_id(), p.get_position())).collect();
+        for (id, position) in positions {
+            self.positions[id] = position;
+        }
+    }
+
+    /// update_bests picks the best performance among the swarms
+    /// and updates the historic best if necessary
+    fn update_bests(&mut self) {
+        let strategy = self.ctx.get_strategy();
+
+        let mut best = self.swarms[0].get_best();
+        for swarm in self.swarms[1..].iter() {
+            best = strategy.pick_best_performance(&best, &swarm.get_best());
+        }
+        self.best = best;
+        if strategy.is_better(&self.best, &self.historic_best) {
+            self.historic_best = self.best;
+            self.historic_samples = self.controller.get_resampling().samples;
+        }
+    }
+
+    pub fn set_params(&mut self, params: ParameterSet) {
+        self.params = params;
+        for swarm in self.swarms.iter_mut() {
+            swarm.set_params(params);
+        }
+    }
+
+    pub fn get_best(&self) -> Performance {
+        self.best
+    }
+
+    pub fn get_historic_best(&self) -> Performance {
+        self.historic_best
+    }
+
+    pub fn get_iteration(&self) -> u64 {
+        self.iteration
+    }
+
+    /// get_pareto_front returns the non-dominated solutions found so far,
+    /// it's empty unless the problem is multi-objective
+    pub fn get_pareto_front(&self) -> Vec<ParetoPoint> {
+        self.archive.get_members().to_vec()
+    }
+
+    /// get_change_count returns how many times a change of the goal was detected
+    pub fn get_change_count(&self) -> usize {
+        self.changes
+    }
+
+    /// get_population_size returns the total amount of particles, excluded ones included
+    pub fn get_population_size(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn get_swarm_count(&self) -> usize {
+        self.swarms.len()
+    }
+
+    pub fn get_swarm_best(&self, idx: usize) -> Result<Performance> {
+        Ok(self.get_swarm(idx)?.get_best())
+    }
+
+    pub fn get_swarm_historic_best(&self, idx: usize) -> Result<Performance> {
+        Ok(self.get_swarm(idx)?.get_historic_best())
+    }
+
+    /// get_collision_count returns the amount of collisions detected by every particle,
+    /// excluded ones included
+    pub fn get_collision_count(&self) -> usize {
+        let swarms: usize = self.swarms.iter().map(|swarm| swarm.get_collision_count()).sum();
+        swarms + self.excluded.iter().map(|p| p.get_collisions()).sum::<usize>()
+    }
+
+    /// get_swarm_members returns the particle ids of the idxth swarm
+    pub fn get_swarm_members(&self, idx: usize) -> Result<Vec<usize>> {
+        Ok(self.get_swarm(idx)?.get_member_ids())
+    }
+
+    /// get_swarm_links returns the communication links of the idxth swarm as a flat list
+    /// of particle id pairs, ie [a0, b0, a1, b1, ...]
+    pub fn get_swarm_links(&self, idx: usize) -> Result<Vec<usize>> {
+        let swarm = self.get_swarm(idx)?;
+        let ids = swarm.get_member_ids();
+        let mut links = Vec::new();
+        for (i, id) in ids.iter().enumerate() {
+            for &j in swarm.get_network().get_links(i) {
+                if i < j {
+                    links.push(*id);
+                    links.push(ids[j]);
+                }
+            }
+        }
+        Ok(links)
+    }
+
+    /// get_excluded_members returns the ids of the socially excluded particles
+    pub fn get_excluded_members(&self) -> Vec<usize> {
+        self.excluded.iter().map(|p| p.get_id()).collect()
+    }
+
+    /// get_position_by_idx returns the position of the particle with the given id
+    pub fn get_position_by_idx(&self, idx: usize) -> Result<Vector> {
+        let idx = Error::check_index(idx, self.positions.len())?;
+        Ok(self.positions[idx])
+    }
+}
+
+impl SubswarmManager {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(ctx: Ctx,
+        params: ParameterSet,
+        swarm_config: SwarmConfig,
+        config: DarwinConfig,
+        controller: ParticleController,
+        particle_config: ParticleConfig,
+        terrain: &dyn HeightField,
+        rng: &mut Rng,
+    ) -> Result<Self> {
+        let mut population = deploy(&swarm_config, &particle_config, &controller, terrain, rng)?;
+        let size = population.len();
+
+        let swarm_count = config.initial_swarms.min(config.max_swarms).max(1);
+        let swarm_size = (size / swarm_count).min(config.max_population).max(1);
+
+        let mut swarms = Vec::with_capacity(swarm_count);
+        for _ in 0..swarm_count {
+            if population.len() < swarm_size {
+                break;
+            }
+            let members = population.drain(..swarm_size).collect();
+            swarms.push(Swarm::from_population(ctx.clone(), params, controller.clone(), members, &swarm_config, terrain, rng)?);
+        }
+
+        let initial_perf = swarms[0].get_best();
+
+        let mut manager = Self {
+            ctx,
+            params,
+            config,
+            controller,
+            swarm_config,
+            swarms,
+            excluded: population,
+            best: initial_perf,
+            historic_best: initial_perf,
+            positions: vec![Vector::new(0.0, 0.0, 0.0); size],
+            iteration: 0,
+            dynamic: DynamicConfig::default(),
+            changes: 0,
+            archive: ParetoArchive::default(),
+            historic_samples: 0,
+        };
+        manager.historic_samples = manager.controller.get_resampling().samples;
+
+        manager.update_positions();
+        manager.update_bests();
+        Ok(manager.with_archive(ArchiveConfig::default()))
+    }
+
+    /// with_archive sets up the Pareto archive of multi-objective problems with the current particles
+    pub fn with_archive(mut self, config: ArchiveConfig) -> Self {
+        self.archive = ParetoArchive::new(config.capacity, self.ctx.get_strategies());
+        self.fill_archive();
+        self
+    }
+
+    /// with_dynamic enables the responses to changes of the goal
+    pub fn with_dynamic(mut self, dynamic: DynamicConfig) -> Self {
+        self.dynamic = dynamic;
+        self
+    }
+
+    pub fn update(&mut self, terrain: &dyn HeightField, rng: &mut Rng) {
+        // particles are about to be scored at the next iteration, the landscape is checked beforehand
+        if self.dynamic.is_enabled() && self.detect_change(self.iteration + 1) {
+            self.respond_to_change(self.iteration + 1, terrain, rng);
+        }
+
+        // bests of noisy goals are measured again before they guide the particles
+        if self.ctx.noise.is_noisy() && self.controller.get_resampling().is_reevaluation(self.iteration + 1) {
+            self.remeasure_bests(self.iteration + 1, terrain, rng);
+        }
+
+        let multi_objective = self.ctx.is_multi_objective();
+        for swarm in self.swarms.iter_mut() {
+            let archive = if multi_objective { Some(&mut self.archive) } else { None };
+            swarm.update(self.iteration, archive, terrain, rng);
+        }
+
+        // excluded particles have no social information, they follow their own best
+        for particle in self.excluded.iter_mut() {
+            let own_best = particle.get_best_performance().position;
+            self.controller.update(particle, own_best, None, &self.params, self.iteration + 1, terrain, rng);
+            if multi_objective && particle.get_violation() == 0.0 {
+                self.archive.insert(particle.get_pareto_point());
+            }
+        }
+
+        self.evolve(terrain, rng);
+        self.update_positions();
+        self.update_bests();
+        self.iteration += 1;
+    }
+
+    pub fn get_positions(&self) -> &Vec<Vector> {
+        &self.positions
+    }
+
+    pub fn get_swarm(&self, idx: usize) -> Result<&Swarm> {
+        let idx = Error::check_index(idx, self.swarms.len())?;
+        Ok(&self.swarms[idx])
+    }
+
+    pub fn get_swarms(&self) -> &[Swarm] {
+        &self.swarms
+    }
+
+    pub fn get_excluded(&self) -> &[ParticleState] {
+        &self.excluded
+    }
+
+    /// get_particles iterates over every particle, the swarms' members first and the excluded ones last
+    pub fn get_particles(&self) -> impl Iterator<Item = &ParticleState> {
+        self.swarms.iter()
+            .flat_map(|swarm| swarm.get_population().iter())
+            .chain(self.excluded.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::goal::{Benchmark, Strategy};
+    use crate::pso::{ControllerConfig, SensorConfig, CommConfig, TopologyConfig, TopologyKind};
+    use crate::terrain;
+    use crate::tests::config;
+    use crate::{SimConfig, Simulator};
+
+    #[test]
+    fn evolution_preserves_particles() {
+        let mut rng = Rng::new(0);
+        let terrain = terrain::Terrain::new(terrain::Config::new_from_size(1000), &mut rng).unwrap();
+        let ctx = Ctx::new(Benchmark::Ackley, Strategy::Minimize);
+        let sensor = SensorConfig::new(10.0, 0.5, std::f64::consts::PI / 6.0, std::f64::consts::PI / 180.0);
+        let controller = ParticleController::new_from_config(ctx.clone(), ControllerConfig::new(sensor));
+        let swarm_config = SwarmConfig::new(12, Vector::new(10.0, 600.0, 10.0), 10.0, 0.5, CommConfig::new(50.0, true, 1.0, 0.8), TopologyConfig::new(TopologyKind::Ring, 1));
+        let darwin = DarwinConfig::new(3, 1, 4, 2, 6, 2);
+        let params = ParameterSet::new(0.5, 2.0, 2.0, 0.0, 0.5, 10.0);
+
+        // the default keeps a single swarm whose members are never excluded
+        let mut single = SubswarmManager::new(
+            ctx.clone(), params, swarm_config, DarwinConfig::default(), controller.clone(), ParticleConfig::new(5), &terrain, &mut rng,
+        ).unwrap();
+        for _ in 0..100 {
+            single.update(&terrain, &mut rng);
+        }
+        assert_eq!(single.get_swarm_count(), 1);
+        assert_eq!(single.get_swarm_members(0).unwrap().len(), 12);
+
+        let mut manager = SubswarmManager::new(
+            ctx, params, swarm_config, darwin, controller, ParticleConfig::new(5), &terrain, &mut rng,
+        ).unwrap();
+
+        for _ in 0..100 {
+            manager.update(&terrain, &mut rng);
+
+            let mut ids = manager.get_excluded_members();
+            for idx in 0..manager.get_swarm_count() {
+                let members = manager.get_swarm_members(idx).unwrap();
+                assert!(members.len() <= darwin.max_population);
+                ids.extend(members);
+            }
+            ids.sort();
+            assert_eq!(ids, (0..12).collect::<Vec<usize>>());
+            assert!(manager.get_swarm_count() >= darwin.min_swarms && manager.get_swarm_count() <= darwin.max_swarms);
+        }
+    }
+
+    #[test]
+    fn detected_changes_trigger_responses() {
+        let moving_peaks = r#"{"moving_peaks": {"change_frequency": 10, "seed": 5}}"#;
+        let mut static_goal = config(2);
+        static_goal.dynamic = DynamicConfig::new(true, 0.0, false);
+        let mut dynamic: SimConfig = serde_json::from_str(&serde_json::to_string(&static_goal).unwrap().replace("\"Ackley\"", moving_peaks)).unwrap();
+        dynamic.ctx.strategy = Strategy::Maximize;
+        let mut unresponsive = dynamic.clone();
+        unresponsive.dynamic = DynamicConfig::default();
+        let mut randomized = dynamic.clone();
+        randomized.dynamic = DynamicConfig::new(false, 0.5, true);
+
+        let mut simulators: Vec<Simulator> = vec![static_goal, dynamic, unresponsive, randomized].into_iter()
+            .map(|config| Simulator::new(config).unwrap())
+            .collect();
+        for _ in 0..35 {
+            simulators.iter_mut().for_each(|simulator| simulator.step());
+        }
+
+        let changes: Vec<usize> = simulators.iter().map(|simulator| simulator.get_goal_change_count()).collect();
+        assert_eq!(changes, vec![0, 3, 0, 3]);
+        assert!(simulators[1].is_goal_dynamic() && !simulators[0].is_goal_dynamic());
+
+        // responses refresh the personal bests, stale ones keep the scores of the initial landscape
+        for simulator in &simulators[1..] {
+            let swarm = simulator.get_swarm();
+            let fresh = swarm.get_particles().all(|p| {
+                let best = p.get_best_performance();
+                best.score == simulator.config.ctx.evaluate_at(best.position.x, best.position.z, 31.0)
+            });
+            assert_eq!(fresh, simulator.config.dynamic.is_enabled());
+        }
+    }
+}
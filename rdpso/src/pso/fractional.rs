@@ -0,0 +1,40 @@
+/// gl_coefficients returns the Grünwald–Letnikov weights of the latest `order` velocities
+/// for a fractional derivative of order alpha, as used by the FDPSO/RDPSO velocity update:
+///
+/// v[t+1] = alpha v[t] + 1/2 alpha (1 - alpha) v[t-1] + 1/6 alpha (1 - alpha) (2 - alpha) v[t-2] + ...
+///
+/// The kth weight is (-1)^k * binomial(alpha, k + 1), computed through the recurrence
+/// w[k] = w[k-1] * (k - alpha) / (k + 1).
+pub fn gl_coefficients(alpha: f64, order: usize) -> Vec<f64> {
+    let mut coefficients = Vec::with_capacity(order);
+    let mut coefficient = alpha;
+
+    for k in 0..order {
+        if k > 0 {
+            coefficient *= (k as f64 - alpha) / (k as f64 + 1.0);
+        }
+        coefficients.push(coefficient);
+    }
+    coefficients
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coefficients_match_fdpso_expansion() {
+        let alpha = 0.6;
+        let coefficients = gl_coefficients(alpha, 4);
+        let expected = [
+            alpha,
+            alpha * (1.0 - alpha) / 2.0,
+            alpha * (1.0 - alpha) * (2.0 - alpha) / 6.0,
+            alpha * (1.0 - alpha) * (2.0 - alpha) * (3.0 - alpha) / 24.0,
+        ];
+
+        for (c, e) in coefficients.iter().zip(expected.iter()) {
+            assert!((c - e).abs() < 1e-12);
+        }
+    }
+}
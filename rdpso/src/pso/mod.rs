@@ -1,9 +1,19 @@
+use std::sync::Arc;
+
 use crate::wasm_bindgen;
 
 use serde::{Serialize, Deserialize};
 
 use crate::space::Vector;
+use crate::error::Result;
+use crate::goal::Benchmark;
+use crate::goal::Criterion;
+use crate::goal::Expression;
 use crate::goal::Goal;
+use crate::goal::GoalInfo;
+use crate::goal::GoalTransform;
+use crate::goal::Objective;
+use crate::goal::Performance;
 use crate::goal::Strategy;
 
 pub mod particle;
@@ -11,30 +21,149 @@ pub mod particle;
 mod swarm;
 pub use swarm::Swarm;
 
+mod darwin;
+pub use darwin::SubswarmManager;
+
+mod network;
+pub use network::Network;
+
+mod fractional;
+
+mod topology;
+pub use topology::Topology;
+
 mod sensor;
 
+mod noise;
+pub use noise::{NoiseKind, NoiseModel, ResamplingConfig};
+
+mod constraint;
+pub use constraint::{Constraint, ConstraintConfig, ConstraintHandling};
+
 /// Ctx models the problem context, with the evaluation function and the strategy
 /// The Ctx is immutable for a run.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 #[wasm_bindgen]
 #[derive(Serialize, Deserialize)]
 pub struct Ctx {
+    #[wasm_bindgen(skip)]
     pub goal: Goal,
     pub strategy: Strategy,
+
+    /// transform places the goal's canonical domain on the terrain
+    #[serde(default)]
+    pub transform: GoalTransform,
+
+    /// objectives are optimized alongside the goal, which makes the problem multi-objective.
+    /// The goal remains the primary objective, the one scores and the Darwinian evolution refer to,
+    /// while particles are guided by the Pareto front of every objective.
+    #[wasm_bindgen(skip)]
+    #[serde(default)]
+    pub objectives: Vec<Criterion>,
+
+    /// noise models the sensor measuring the goal, evaluate stays exact while particles measure
+    #[serde(default)]
+    pub noise: NoiseModel,
 }
 
 #[wasm_bindgen]
 impl Ctx {
-    pub fn new(goal: Goal, strategy: Strategy) -> Self {
+    pub fn new(benchmark: Benchmark, strategy: Strategy) -> Self {
+        Self::new_with_transform(benchmark, strategy, GoalTransform::identity())
+    }
+
+    pub fn new_with_transform(benchmark: Benchmark, strategy: Strategy, transform: GoalTransform) -> Self {
+        Self::from_goal(Goal::Benchmark(benchmark), strategy, transform)
+    }
+
+    /// new_from_expression parses a user defined goal, ie `sin(x/10)*cos(z/10)`
+    pub fn new_from_expression(expression: &str, strategy: Strategy, transform: GoalTransform) -> Result<Ctx> {
+        Ok(Self::from_goal(Goal::Expression(Expression::parse(expression)?), strategy, transform))
+    }
+
+    /// get_benchmark returns the built-in goal, if any
+    pub fn get_benchmark(&self) -> Option<Benchmark> {
+        self.goal.get_benchmark()
+    }
+
+    pub fn get_goal_name(&self) -> String { self.get_objective().name() }
+
+    pub fn get_strategy(&self) -> Strategy { self.strategy }
+
+    pub fn get_transform(&self) -> GoalTransform { self.transform }
+
+    /// is_multi_objective reports whether objectives were set alongside the goal
+    pub fn is_multi_objective(&self) -> bool { !self.objectives.is_empty() }
+
+    /// evaluate scores a world position at the start of the run
+    pub fn evaluate(&self, x: f64, z: f64) -> f64 {
+        self.evaluate_at(x, z, 0.0)
+    }
+
+    /// evaluate_at scores a world position at time t, the objective is evaluated through the ctx's transform
+    pub fn evaluate_at(&self, x: f64, z: f64, t: f64) -> f64 {
+        let (x, z) = self.transform.to_canonical(x, z);
+        self.goal.evaluate_at(x, z, t)
+    }
+
+    /// evaluate_objectives_at scores a world position at time t for every objective, the goal first
+    pub fn evaluate_objectives_at(&self, x: f64, z: f64, t: f64) -> Vec<f64> {
+        let (cx, cz) = self.transform.to_canonical(x, z);
+        std::iter::once(self.evaluate_at(x, z, t))
+            .chain(self.objectives.iter().map(|criterion| criterion.goal.evaluate_at(cx, cz, t)))
+            .collect()
+    }
+
+    /// get_optima returns the objective's known optima in world coordinates
+    pub fn get_optima(&self) -> Vec<Performance> {
+        let optima = match &self.goal {
+            Goal::Benchmark(benchmark) => GoalInfo::new(*benchmark).get_optima(),
+            goal => goal.optimum().into_iter().collect(),
+        };
+
+        optima.into_iter()
+            .map(|optimum| {
+                let (x, z) = self.transform.to_world(optimum.position.x, optimum.position.z);
+                Performance::new(Vector::new(x, 0.0, z), optimum.score)
+            })
+            .collect()
+    }
+}
+
+impl Ctx {
+    pub fn from_goal(goal: Goal, strategy: Strategy, transform: GoalTransform) -> Self {
         Self {
             goal,
             strategy,
+            transform,
+            objectives: Vec::new(),
+            noise: NoiseModel::default(),
         }
     }
 
-    fn get_goal(&self) -> Goal { self.goal }
+    /// get_strategies returns the strategy of every objective, the goal first
+    pub fn get_strategies(&self) -> Vec<Strategy> {
+        std::iter::once(self.strategy)
+            .chain(self.objectives.iter().map(|criterion| criterion.strategy))
+            .collect()
+    }
+
+    /// with_objective builds a context around a custom objective, see Goal::Custom
+    pub fn with_objective(objective: Arc<dyn Objective>, strategy: Strategy, transform: GoalTransform) -> Self {
+        Self::from_goal(Goal::Custom(objective), strategy, transform)
+    }
 
-    fn get_strategy(&self) -> Strategy { self.strategy }
+    pub fn get_goal(&self) -> &Goal {
+        &self.goal
+    }
+
+    /// get_objective returns the goal as a shared objective
+    pub fn get_objective(&self) -> Arc<dyn Objective> {
+        match &self.goal {
+            Goal::Custom(objective) => objective.clone(),
+            goal => Arc::new(goal.clone()),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -46,20 +175,63 @@ pub struct ParameterSet {
     pub c1: f64,
     pub c2: f64,
     pub c3: f64,
+
+    /// c4 weights the term which keeps the communication network connected
+    #[serde(default)]
+    pub c4: f64,
     pub max_velocity: f64,
+
+    /// velocity_mode selects how the previous velocities contribute to the new one
+    #[serde(default)]
+    pub velocity_mode: VelocityMode,
+
+    /// alpha is the fractional coefficient, only used by VelocityMode::Fractional
+    #[serde(default)]
+    pub alpha: f64,
+
+    /// fractional_order is the amount of past velocities considered by VelocityMode::Fractional,
+    /// it can't exceed ParticleConfig::position_log_size, the amount of velocities particles keep
+    #[serde(default)]
+    pub fractional_order: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
+/// VelocityMode selects the velocity update equation
+pub enum VelocityMode {
+    /// classic PSO, the previous velocity is weighted by the inertia w
+    #[default]
+    Inertia = 0,
+
+    /// fractional order PSO, the latest velocities are weighted by the
+    /// Grünwald–Letnikov coefficients of order alpha
+    Fractional = 1,
 }
 
 #[wasm_bindgen]
 impl ParameterSet{
-    pub fn new(w: f64, c1: f64, c2: f64, c3: f64, max_velocity: f64) -> Self {
+    pub fn new(w: f64, c1: f64, c2: f64, c3: f64, c4: f64, max_velocity: f64) -> Self {
         Self {
             w,
             c1,
             c2,
             c3,
+            c4,
             max_velocity,
+            velocity_mode: VelocityMode::Inertia,
+            alpha: 0.0,
+            fractional_order: 0,
         }
     }
+
+    /// with_fractional_order switches the velocity update to the fractional order one
+    pub fn with_fractional_order(mut self, alpha: f64, order: usize) -> Self {
+        self.velocity_mode = VelocityMode::Fractional;
+        self.alpha = alpha;
+        self.fractional_order = order;
+        self
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -124,25 +296,123 @@ pub struct SwarmConfig {
 
     /// magnitude of the swarm's particle's initial velocity
     pub initial_swarm_velocity: f64,
+
+    /// communication model between the particles
+    #[serde(default)]
+    pub comm: CommConfig,
+
+    /// neighbourhood topology, which defines who informs each particle
+    #[serde(default)]
+    pub topology: TopologyConfig,
 }
 
 #[wasm_bindgen]
 impl SwarmConfig {
-    pub fn new(size: usize, deploy_position: Vector, deploy_spread_radius: f64, initial_swarm_velocity: f64) -> Self {
+    pub fn new(
+        size: usize,
+        deploy_position: Vector,
+        deploy_spread_radius: f64,
+        initial_swarm_velocity: f64,
+        comm: CommConfig,
+        topology: TopologyConfig,
+    ) -> Self {
     Self {
         size,
         deploy_position,
         deploy_spread_radius,
         initial_swarm_velocity,
+        comm,
+        topology,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
+/// TopologyKind enumerates the supported neighbourhood topologies
+pub enum TopologyKind {
+    /// every particle is informed by the whole swarm (global best)
+    #[default]
+    Star = 0,
+
+    /// particles are informed by the k closest indexes on each side of a ring
+    Ring = 1,
+
+    /// particles are laid on a toroidal grid and informed by the 4 adjacent cells
+    VonNeumann = 2,
+
+    /// each particle informs k random particles, re-drawn once whenever the swarm starts stagnating
+    Random = 3,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+/// TopologyConfig selects the neighbourhood topology of the swarms
+pub struct TopologyConfig {
+    pub kind: TopologyKind,
+
+    /// neighbours is the k parameter of the Ring and Random topologies
+    pub neighbours: usize,
+}
+
+#[wasm_bindgen]
+impl TopologyConfig {
+    pub fn new(kind: TopologyKind, neighbours: usize) -> Self {
+        Self {
+            kind,
+            neighbours,
         }
     }
 }
 
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+/// CommConfig models the communication constraints between particles.
+/// When disabled every particle instantly knows the swarm's best.
+pub struct CommConfig {
+    /// enabled turns the communication constraints on
+    pub enabled: bool,
+
+    /// range is the maximum distance between two particles which can communicate
+    pub range: f64,
+
+    /// line_of_sight requires the terrain not to block the segment between two particles
+    pub line_of_sight: bool,
+
+    /// los_step_size is the sampling step used when checking the line of sight
+    pub los_step_size: f64,
+
+    /// connectivity_threshold is the fraction of the range beyond which a particle
+    /// is steered towards its nearest mate, weighted by c4
+    pub connectivity_threshold: f64,
+}
+
+#[wasm_bindgen]
+impl CommConfig {
+    pub fn new(range: f64, line_of_sight: bool, los_step_size: f64, connectivity_threshold: f64) -> Self {
+        Self {
+            enabled: true,
+            range,
+            line_of_sight,
+            los_step_size,
+            connectivity_threshold,
+        }
+    }
+
+    /// unconstrained returns a config where every particle can always communicate
+    pub fn unconstrained() -> Self {
+        Self::default()
+    }
+}
+
 #[wasm_bindgen]
 #[derive(Clone, Copy, Debug)]
 #[derive(Serialize, Deserialize)]
 pub struct ParticleConfig {
-    /// how many positions to keep in the position history buffer
+    /// how many positions, and velocities, to keep in the history buffers
     pub position_log_size: usize,
 }
 
@@ -154,4 +424,122 @@ impl ParticleConfig {
         }
     }
 
-}
\ No newline at end of file
+}
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize)]
+/// DarwinConfig sets the parameters of the Darwinian subswarm evolution (RDPSO)
+pub struct DarwinConfig {
+    /// number of subswarms the particles are split into at deployment
+    pub initial_swarms: usize,
+
+    /// lower bound of subswarms, a stagnating swarm is only deleted above it
+    pub min_swarms: usize,
+
+    /// upper bound of subswarms, an improving swarm only spawns a new one below it
+    pub max_swarms: usize,
+
+    /// lower bound of particles per subswarm, below which a swarm is deleted instead of losing members
+    pub min_population: usize,
+
+    /// upper bound of particles per subswarm
+    pub max_population: usize,
+
+    /// amount of iterations without improvement after which a swarm is punished
+    pub stagnation_threshold: usize,
+}
+
+/// the default is a single swarm which never evolves, as before subswarms existed
+impl Default for DarwinConfig {
+    fn default() -> Self {
+        Self {
+            initial_swarms: 1,
+            min_swarms: 1,
+            max_swarms: 1,
+            min_population: 1,
+            max_population: usize::MAX,
+            stagnation_threshold: usize::MAX,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl DarwinConfig {
+    pub fn new(
+        initial_swarms: usize,
+        min_swarms: usize,
+        max_swarms: usize,
+        min_population: usize,
+        max_population: usize,
+        stagnation_threshold: usize,
+    ) -> Self {
+        Self {
+            initial_swarms,
+            min_swarms,
+            max_swarms,
+            min_population,
+            max_population,
+            stagnation_threshold,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+/// DynamicConfig sets how the swarms respond when the goal changes over time.
+/// A change is detected when the historic best no longer scores what it used to,
+/// detection is only performed if at least one response is enabled.
+pub struct DynamicConfig {
+    /// reevaluate scores the personal bests again, so that they reflect the new landscape
+    pub reevaluate: bool,
+
+    /// randomize_fraction is the fraction of particles moved to a random position of the terrain
+    pub randomize_fraction: f64,
+
+    /// reset_memory replaces the personal bests with the current positions
+    pub reset_memory: bool,
+}
+
+#[wasm_bindgen]
+impl DynamicConfig {
+    pub fn new(reevaluate: bool, randomize_fraction: f64, reset_memory: bool) -> Self {
+        Self {
+            reevaluate,
+            randomize_fraction,
+            reset_memory,
+        }
+    }
+
+    /// is_enabled reports whether any response is set, ie whether changes need to be detected
+    pub fn is_enabled(&self) -> bool {
+        self.reevaluate || self.reset_memory || self.randomize_fraction > 0.0
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+/// ArchiveConfig sets the external archive of multi-objective problems
+pub struct ArchiveConfig {
+    /// capacity is the maximum amount of non-dominated solutions kept,
+    /// the most crowded ones are pruned beyond it
+    pub capacity: usize,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 100,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl ArchiveConfig {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::physics::collision::LinearDetector;
+use serde::{Serialize, Deserialize};
+use crate::space::Vector;
+use crate::goal::{Performance, Strategy};
+use crate::terrain::HeightField;
+use super::CommConfig;
+
+/// Network models the communication graph (MANET) between the particles of a swarm.
+/// Two particles are linked if they are within communication range
+/// and, optionally, if the terrain doesn't block the line of sight between them.
+/// Information propagates through the links, thus particles in the same
+/// connected component share their knowledge.
+#[derive(Debug, Clone, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct Network {
+    /// links is the adjacency list of the graph, indexed by the particle's index in the swarm
+    links: Vec<Vec<usize>>,
+
+    /// components maps each particle to the id of the connected component it belongs to
+    components: Vec<usize>,
+}
+
+impl Network {
+
+    /// build computes the connectivity graph for the given positions.
+    /// If communication is not constrained every particle belongs to the same component
+    /// and no links are stored.
+    pub fn build(positions: &[Vector], config: &CommConfig, terrain: &dyn HeightField) -> Self {
+        let size = positions.len();
+        if !config.enabled {
+            return Self {
+                links: vec![Vec::new(); size],
+                components: vec![0; size],
+            };
+        }
+
+        let mut links = vec![Vec::new(); size];
+        for i in 0..size {
+            for j in (i + 1)..size {
+                if Self::can_communicate(positions[i], positions[j], config, terrain) {
+                    links[i].push(j);
+                    links[j].push(i);
+                }
+            }
+        }
+
+        let components = Self::find_components(&links);
+        Self {
+            links,
+            components,
+        }
+    }
+
+    /// can_communicate reports whether a and b are in range and, if required, in line of sight
+    fn can_communicate(a: Vector, b: Vector, config: &CommConfig, terrain: &dyn HeightField) -> bool {
+        let distance = (b - a).magnitude();
+        if distance > config.range {
+            return false;
+        }
+
+        if !config.line_of_sight || distance < config.los_step_size {
+            return true;
+        }
+
+        let detector = LinearDetector::new(distance, config.los_step_size);
+        !detector.has_collision(a, b - a, terrain)
+    }
+
+    /// find_components labels each node with its connected component through a BFS
+    fn find_components(links: &[Vec<usize>]) -> Vec<usize> {
+        let mut components = vec![usize::MAX; links.len()];
+        let mut queue = std::collections::VecDeque::new();
+        let mut label = 0;
+
+        for start in 0..links.len() {
+            if components[start] != usize::MAX {
+                continue;
+            }
+
+            components[start] = label;
+            queue.push_back(start);
+            while let Some(node) = queue.pop_front() {
+                for &neighbour in links[node].iter() {
+                    if components[neighbour] == usize::MAX {
+                        components[neighbour] = label;
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+            label += 1;
+        }
+        components
+    }
+
+    /// reachable_bests returns, for every particle, the best performance
+    /// among the particles it can reach through the network
+    pub fn reachable_bests(&self, performances: &[Performance], strategy: Strategy) -> Vec<Performance> {
+        let component_count = self.components.iter().max().map_or(0, |max| max + 1);
+        let mut bests: Vec<Option<Performance>> = vec![None; component_count];
+
+        for (performance, &component) in performances.iter().zip(self.components.iter()) {
+            bests[component] = match bests[component] {
+                Some(best) => Some(strategy.pick_best_performance(&best, performance)),
+                None => Some(*performance),
+            };
+        }
+
+        self.components.iter()
+            .map(|&component| bests[component].unwrap())
+            .collect()
+    }
+
+    /// get_links returns the indexes of the particles linked to the idxth particle
+    pub fn get_links(&self, idx: usize) -> &[usize] {
+        &self.links[idx]
+    }
+
+    pub fn get_component(&self, idx: usize) -> usize {
+        self.components[idx]
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.components.iter().all(|&component| component == 0)
+    }
+}
+
+/// connectivity_target returns the position of the nearest swarm mate
+/// if it is farther than the threshold fraction of the communication range.
+/// Steering towards it keeps the network from breaking apart, as in the RDPSO
+/// connectivity preserving term.
+pub fn connectivity_target(idx: usize, positions: &[Vector], config: &CommConfig) -> Option<Vector> {
+    if !config.enabled {
+        return None;
+    }
+
+    let position = positions[idx];
+    let nearest = positions.iter()
+        .enumerate()
+        .filter(|(i, _)| *i != idx)
+        .map(|(_, mate)| (*mate, (*mate - position).magnitude()))
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    if nearest.1 > config.connectivity_threshold * config.range {
+        Some(nearest.0)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain;
+
+    #[test]
+    fn out_of_range_particles_do_not_share_bests() {
+        let terrain = terrain::Terrain::new(terrain::Config::new_from_size(1000), &mut crate::utils::Rng::new(0)).unwrap();
+        let config = CommConfig::new(10.0, false, 1.0, 0.8);
+        let positions = [Vector::new(0.0, 0.0, 0.0), Vector::new(5.0, 0.0, 0.0), Vector::new(100.0, 0.0, 0.0)];
+        let performances: Vec<Performance> = positions.iter()
+            .zip([3.0, 1.0, 2.0])
+            .map(|(p, score)| Performance::new(*p, score))
+            .collect();
+
+        let network = Network::build(&positions, &config, &terrain);
+        let bests = network.reachable_bests(&performances, Strategy::Minimize);
+
+        assert!(!network.is_connected());
+        assert_eq!(bests[0].score, 1.0);
+        assert_eq!(bests[1].score, 1.0);
+        assert_eq!(bests[2].score, 2.0);
+        assert_eq!(connectivity_target(2, &positions, &config), Some(positions[1]));
+        assert_eq!(connectivity_target(0, &positions, &config), None);
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Serialize, Deserialize};
+
+use crate::wasm_bindgen;
+use crate::utils::Rng;
+
+/// NoiseKind enumerates the measurement noise models
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+pub enum NoiseKind {
+    /// None makes measurements exact
+    #[default]
+    None = 0,
+
+    /// Gaussian adds a normal error of standard deviation sigma, f + σN
+    Gaussian = 1,
+
+    /// Multiplicative scales the value by a normal error, f(1 + σN), ie sensors with a relative accuracy
+    Multiplicative = 2,
+
+    /// HeavyTailed adds a Cauchy error of scale sigma, which produces occasional large outliers
+    HeavyTailed = 3,
+}
+
+/// NoiseModel turns exact goal values into sensor measurements
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct NoiseModel {
+    pub kind: NoiseKind,
+    pub sigma: f64,
+}
+
+#[wasm_bindgen]
+impl NoiseModel {
+    pub fn new(kind: NoiseKind, sigma: f64) -> Self {
+        Self {
+            kind,
+            sigma,
+        }
+    }
+
+    pub fn is_noisy(&self) -> bool {
+        self.kind != NoiseKind::None && self.sigma != 0.0
+    }
+}
+
+impl NoiseModel {
+
+    /// measure returns the mean of samples measurements of an exact value.
+    /// Exact models don't consume randomness.
+    pub fn measure(&self, value: f64, samples: usize, rng: &mut Rng) -> f64 {
+        if !self.is_noisy() {
+            return value;
+        }
+
+        let samples = samples.max(1);
+        let total: f64 = (0..samples)
+            .map(|_| match self.kind {
+                NoiseKind::None => value,
+                NoiseKind::Gaussian => value + self.sigma * rng.gen_gaussian(),
+                NoiseKind::Multiplicative => value * (1.0 + self.sigma * rng.gen_gaussian()),
+                NoiseKind::HeavyTailed => value + self.sigma * libm::tan(std::f64::consts::PI * (rng.gen_random() - 0.5)),
+            })
+            .sum();
+        total / samples as f64
+    }
+}
+
+fn default_samples() -> usize {
+    1
+}
+
+/// ResamplingConfig sets how noisy measurements are handled
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ResamplingConfig {
+    /// samples is the amount of measurements averaged for every evaluation
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+
+    /// reevaluation_interval measures the personal and global bests again every that many iterations,
+    /// 0 disables it
+    #[serde(default)]
+    pub reevaluation_interval: u64,
+
+    /// running_mean averages every measurement of a best instead of keeping the latest one
+    #[serde(default)]
+    pub running_mean: bool,
+}
+
+impl Default for ResamplingConfig {
+    fn default() -> Self {
+        Self::new(default_samples(), 0, false)
+    }
+}
+
+#[wasm_bindgen]
+impl ResamplingConfig {
+    pub fn new(samples: usize, reevaluation_interval: u64, running_mean: bool) -> Self {
+        Self {
+            samples,
+            reevaluation_interval,
+            running_mean,
+        }
+    }
+
+    /// is_reevaluation reports whether the bests are measured again at the given iteration
+    pub fn is_reevaluation(&self, iteration: u64) -> bool {
+        self.reevaluation_interval > 0 && iteration.is_multiple_of(self.reevaluation_interval)
+    }
+}
+
+impl ResamplingConfig {
+
+    /// update_best merges a new measurement of a best, made of the configured amount of samples,
+    /// with the previous score of the best, which is made of count samples
+    pub fn update_best(&self, score: f64, count: usize, measurement: f64) -> (f64, usize) {
+        if !self.running_mean || count == 0 {
+            return (measurement, self.samples.max(1));
+        }
+        let samples = self.samples.max(1);
+        let total = count + samples;
+        ((score * count as f64 + measurement * samples as f64) / total as f64, total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::config;
+    use crate::{pso, Simulator};
+
+    #[test]
+    fn resampling_tames_the_noise() {
+        let mut rng = Rng::new(1);
+        let exact = NoiseModel::default();
+        let gaussian = NoiseModel::new(NoiseKind::Gaussian, 2.0);
+        assert_eq!(exact.measure(5.0, 10, &mut rng), 5.0);
+        assert_eq!(rng, Rng::new(1));
+
+        // the standard error of a mean of 100 samples is σ / 10
+        let error = |samples: usize, rng: &mut Rng| (0..200)
+            .map(|_| (gaussian.measure(5.0, samples, rng) - 5.0).powi(2))
+            .sum::<f64>() / 200.0;
+        let (single, averaged) = (error(1, &mut rng).sqrt(), error(100, &mut rng).sqrt());
+        assert!((single - 2.0).abs() < 0.4 && (averaged - 0.2).abs() < 0.05, "{} {}", single, averaged);
+
+        let resampling = ResamplingConfig::new(2, 5, true);
+        assert_eq!(resampling.update_best(4.0, 2, 1.0), (2.5, 4));
+        assert!(resampling.is_reevaluation(10) && !resampling.is_reevaluation(11));
+    }
+
+    #[test]
+    fn remeasured_bests_shed_lucky_measurements() {
+        let run = |resampling: pso::ResamplingConfig| {
+            let mut config = config(9);
+            config.ctx.noise = pso::NoiseModel::new(pso::NoiseKind::Gaussian, 2.0);
+            config.resampling = resampling;
+            let mut simulator = Simulator::new(config).unwrap();
+            for _ in 0..60 {
+                simulator.step();
+            }
+            let best = simulator.get_swarm().get_historic_best();
+            let exact = simulator.config.ctx.evaluate_at(best.position.x, best.position.z, 60.0);
+            (best, (best.score - exact).abs())
+        };
+
+        // the minimum of many noisy measurements is biased low, measuring bests again averages the luck away
+        let (lucky, lucky_error) = run(pso::ResamplingConfig::default());
+        let (averaged, averaged_error) = run(pso::ResamplingConfig::new(2, 5, true));
+        assert!(averaged_error < lucky_error, "{} {}", averaged_error, lucky_error);
+        assert_eq!(run(pso::ResamplingConfig::default()).0, lucky);
+        assert_ne!(averaged, lucky);
+    }
+}
@@ -1,19 +1,26 @@
-use crate::{wasm_bindgen, physics::collision::LinearDetector, space::Boundary};
+use crate::{wasm_bindgen, physics::collision::LinearDetector};
+use serde::{Serialize, Deserialize};
 
 use circular_queue::CircularQueue;
 
 use crate::space::Vector;
-use super::{ParameterSet, ControllerConfig};
-use crate::goal::Performance;
+use super::{ParameterSet, ControllerConfig, DynamicConfig, VelocityMode};
+use super::{ConstraintConfig, ConstraintHandling, ResamplingConfig};
+use super::fractional::gl_coefficients;
+use crate::goal::{Performance, ParetoPoint, dominates};
 use super::Ctx;
-use crate::utils::gen_random;
+use crate::utils::Rng;
 use super::sensor::CollisionSensor;
-use crate::terrain::Terrain;
+use crate::terrain::HeightField;
 use std::f64::consts;
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
 pub struct ParticleState {
+    /// id uniquely identifies the particle, regardless of which swarm it belongs to
+    id: usize,
+
     /// p represents the current position vector
     p: Vector,
 
@@ -28,36 +35,105 @@ pub struct ParticleState {
 
     positions: CircularQueue<Vector>,
 
+    /// velocities holds the latest velocities, the current one first,
+    /// they make up the memory of VelocityMode::Fractional
+    velocities: CircularQueue<Vector>,
+
     collisions: usize,
 
+    /// collided reports whether the last update was blocked by the terrain
+    collided: bool,
+
+    /// scores and best_scores hold the score of every objective at the current position
+    /// and at the personal best, they are empty unless the problem is multi-objective
+    #[serde(default)]
+    scores: Vec<f64>,
+    #[serde(default)]
+    best_scores: Vec<f64>,
+
+    /// violation measures how far the current position is from satisfying the constraints
+    #[serde(default)]
+    violation: f64,
+
+    /// best_samples counts the measurements averaged into the personal best's score
+    #[serde(default)]
+    best_samples: usize,
 }
 
 impl ParticleState {
 
     /// new initializes a particle
-    pub fn new(p0: Vector, v0: Vector, history_amount: usize, controller: &ParticleController) -> Self {
-        let f_result = controller.ctx.goal.evaluate(p0.x, p0.z);
+    pub fn new(id: usize, p0: Vector, v0: Vector, history_amount: usize, controller: &ParticleController, terrain: &dyn HeightField, rng: &mut Rng) -> Self {
+        let evaluation = controller.evaluate(p0, 0, terrain, rng);
+        let mut velocities = CircularQueue::with_capacity(history_amount);
+        velocities.push(v0);
         Self {
+            id,
             p: p0,
-            best_performance: Performance::new(p0, f_result),
+            best_performance: evaluation.performance(p0),
             v: v0,
-            score: f_result,
+            score: evaluation.score,
             positions: CircularQueue::with_capacity(history_amount),
+            velocities,
             collisions: 0,
+            collided: false,
+            best_scores: evaluation.scores.clone(),
+            scores: evaluation.scores,
+            violation: evaluation.violation,
+            best_samples: controller.resampling.samples,
         }
     }
 
 
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn get_collisions(&self) -> usize {
+        self.collisions
+    }
+
     pub fn get_best_performance(&self) -> Performance {
         self.best_performance
     }
 
     /// get_performance returns the particles current performance
     pub fn get_performance(&self) -> Performance {
-        Performance::new(self.p, self.score)
+        Performance::new_with_violation(self.p, self.score, self.violation)
+    }
+
+    /// get_violation returns how far the current position is from satisfying the constraints
+    pub fn get_violation(&self) -> f64 {
+        self.violation
     }
     pub fn get_position(&self) -> Vector {
-        self.p.into()
+        self.p
+    }
+
+    pub fn get_velocity(&self) -> Vector {
+        self.v
+    }
+
+    pub fn get_score(&self) -> f64 {
+        self.score
+    }
+
+    /// get_scores returns the score of every objective, the goal first, for multi-objective problems
+    pub fn get_scores(&self) -> &[f64] {
+        &self.scores
+    }
+
+    pub fn get_best_scores(&self) -> &[f64] {
+        &self.best_scores
+    }
+
+    /// get_pareto_point returns the current position along with the score of every objective
+    pub fn get_pareto_point(&self) -> ParetoPoint {
+        ParetoPoint::new(self.p, self.scores.clone())
+    }
+
+    pub fn has_collided(&self) -> bool {
+        self.collided
     }
 
     pub fn get_particle_history(&self) -> impl Iterator<Item=&Vector> {
@@ -67,22 +143,64 @@ impl ParticleState {
 
 #[wasm_bindgen]
 #[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize)]
 pub struct ParticleController {
     /// ctx describes the problem space the particle is exploring
     ctx: Ctx,
 
     sensor: CollisionSensor,
+
+    /// constraints restrict the positions the particle may take
+    #[serde(default)]
+    constraints: ConstraintConfig,
+
+    /// resampling sets how noisy measurements are handled
+    #[serde(default)]
+    resampling: ResamplingConfig,
+}
+
+/// Evaluation is the outcome of scoring a position
+struct Evaluation {
+    score: f64,
+
+    /// scores holds every objective's score for multi-objective problems, the goal first
+    scores: Vec<f64>,
+    violation: f64,
+}
+
+impl Evaluation {
+    fn performance(&self, p: Vector) -> Performance {
+        Performance::new_with_violation(p, self.score, self.violation)
+    }
 }
 
 impl ParticleController{
     /// new initializes a particle
     pub fn new(ctx: Ctx, sensor: CollisionSensor) -> Self {
         Self {
-            ctx: ctx,
-            sensor: sensor,
+            ctx,
+            sensor,
+            constraints: ConstraintConfig::default(),
+            resampling: ResamplingConfig::default(),
         }
     }
 
+    /// with_resampling sets how noisy measurements are handled, see Ctx::noise
+    pub fn with_resampling(mut self, resampling: ResamplingConfig) -> Self {
+        self.resampling = resampling;
+        self
+    }
+
+    pub fn get_resampling(&self) -> ResamplingConfig {
+        self.resampling
+    }
+
+    /// with_constraints restricts the positions particles may take
+    pub fn with_constraints(mut self, constraints: ConstraintConfig) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
     pub fn new_from_config(ctx: Ctx, config: ControllerConfig) -> Self {
         let linear_detector = LinearDetector::new(config.collision.range, config.collision.linear_step_size);
         let sensor = CollisionSensor::new(config.collision.fov_angle, config.collision.angular_step_size, linear_detector);
@@ -90,29 +208,166 @@ impl ParticleController{
     }
 
 
-    /// update moves the particle to the next position according to the PSO kinematics equations
-    pub fn update(&mut self, state: &mut ParticleState, g_best: Vector, params: &ParameterSet, boundary: &Boundary, terrain: &Terrain) {
-        let mut v_prime = self.calc_new_velocity(g_best, params, state, terrain);
+    /// update moves the particle to the next position according to the PSO kinematics equations.
+    /// g_best is the best position known to the particle, connectivity_target the position
+    /// it should approach in order to keep the communication network connected, if any.
+    /// The new position is scored at the given iteration.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(&mut self, state: &mut ParticleState, g_best: Vector, connectivity_target: Option<Vector>, params: &ParameterSet, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) {
+        let mut v_prime = self.calc_new_velocity(g_best, connectivity_target, params, state, terrain, rng);
         let p_prime = state.p + v_prime;
-        let mut p_prime = boundary.clip(&p_prime);
+        let mut p_prime = terrain.get_boundary().clip(&p_prime);
+        if self.constraints.handling == ConstraintHandling::Repair && !self.constraints.rules.is_empty() {
+            let repaired = self.constraints.repair(p_prime, iteration as f64, terrain);
+            p_prime = terrain.get_boundary().clip(&repaired);
+        }
 
-        if self.check_collision(p_prime, terrain) {
+        state.collided = self.check_collision(p_prime, terrain);
+        if state.collided {
             v_prime = 0.5 * v_prime.rotate_xz(consts::PI / 2.0);
             state.collisions += 1;
             p_prime = state.p;
-        } 
+        }
         
-        let score = self.ctx.goal.evaluate(p_prime.x, p_prime.z);
-        let current_performance = Performance::new(p_prime, score);
+        let evaluation = self.evaluate(p_prime, iteration, terrain, rng);
+        let current_performance = evaluation.performance(p_prime);
 
         state.p = p_prime;
         state.v = v_prime;
-        state.score = score;
-        state.best_performance = self.ctx.get_strategy().pick_best_performance(&state.best_performance, &current_performance);
+        state.velocities.push(v_prime);
+        state.score = evaluation.score;
+        state.scores = evaluation.scores;
+        state.violation = evaluation.violation;
+        if self.ctx.is_multi_objective() {
+            // when neither dominates the other, the personal best is replaced at random
+            let (current, best) = ((state.scores.as_slice(), state.violation), (state.best_scores.as_slice(), state.best_performance.violation));
+            if self.dominates(current, best) || (!self.dominates(best, current) && rng.gen_random() < 0.5) {
+                state.best_performance = current_performance;
+                state.best_scores = state.scores.clone();
+                state.best_samples = self.resampling.samples;
+            }
+        } else if self.ctx.get_strategy().is_better(&current_performance, &state.best_performance) {
+            state.best_performance = current_performance;
+            state.best_samples = self.resampling.samples;
+        }
         state.positions.push(p_prime);
     }
 
-    fn check_collision(&self, pos: Vector, terrain: &Terrain) -> bool {
+    /// respond_to_change scores the particle again at the given iteration, after the goal changed.
+    /// The personal best is either re-evaluated or, if reset_memory is set, forgotten.
+    pub fn respond_to_change(&self, state: &mut ParticleState, config: &DynamicConfig, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) {
+        let evaluation = self.evaluate(state.p, iteration, terrain, rng);
+        state.score = evaluation.score;
+        state.scores = evaluation.scores;
+        state.violation = evaluation.violation;
+        let current_performance = state.get_performance();
+
+        if config.reset_memory {
+            state.best_performance = current_performance;
+            state.best_scores = state.scores.clone();
+            state.best_samples = self.resampling.samples;
+        } else if config.reevaluate {
+            state.best_samples = self.resampling.samples;
+            let position = state.best_performance.position;
+            let best = self.evaluate(position, iteration, terrain, rng);
+            if self.ctx.is_multi_objective() {
+                let replaced = self.dominates((&state.scores, state.violation), (&best.scores, best.violation));
+                state.best_performance = if replaced { current_performance } else { best.performance(position) };
+                state.best_scores = if replaced { state.scores.clone() } else { best.scores };
+            } else {
+                state.best_performance = self.ctx.get_strategy().pick_best_performance(&best.performance(position), &current_performance);
+            }
+        }
+    }
+
+    /// relocate moves the particle to a random position of the terrain and makes it its personal best.
+    /// The particle keeps its altitude, unless it would be below the terrain.
+    pub fn relocate(&self, state: &mut ParticleState, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) {
+        let boundary = terrain.get_boundary();
+        let x = boundary.get_min_x() + (boundary.get_max_x() - boundary.get_min_x()) * rng.gen_random();
+        let z = boundary.get_min_z() + (boundary.get_max_z() - boundary.get_min_z()) * rng.gen_random();
+        let y = state.p.y.max(terrain.get_height(x, z) + 1.0);
+
+        state.p = Vector::new(x, y, z);
+        let evaluation = self.evaluate(state.p, iteration, terrain, rng);
+        state.score = evaluation.score;
+        state.best_scores = evaluation.scores.clone();
+        state.best_samples = self.resampling.samples;
+        state.scores = evaluation.scores;
+        state.violation = evaluation.violation;
+        state.best_performance = state.get_performance();
+        state.positions.push(state.p);
+    }
+
+    /// remeasure_best measures the personal best again, the new measurement either replaces
+    /// its score or is merged into a running mean, see ResamplingConfig
+    pub fn remeasure_best(&self, state: &mut ParticleState, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) {
+        let position = state.best_performance.position;
+        let measurement = self.evaluate(position, iteration, terrain, rng);
+        let count = state.best_samples;
+
+        let (score, samples) = self.resampling.update_best(state.best_performance.score, count, measurement.score);
+        state.best_performance = Performance::new_with_violation(position, score, measurement.violation);
+        state.best_samples = samples;
+        for (best, &measured) in state.best_scores.iter_mut().zip(measurement.scores.iter()) {
+            *best = self.resampling.update_best(*best, count, measured).0;
+        }
+    }
+
+    /// remeasure measures a stored best again, ie a swarm's historic best,
+    /// count is the amount of measurements averaged into its score
+    pub fn remeasure(&self, best: &mut Performance, count: &mut usize, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) {
+        let measurement = self.evaluate(best.position, iteration, terrain, rng);
+        let (score, samples) = self.resampling.update_best(best.score, *count, measurement.score);
+        *best = Performance::new_with_violation(best.position, score, measurement.violation);
+        *count = samples;
+    }
+
+    /// evaluate measures a position at the given iteration, along with its constraint violation.
+    /// With penalties the violation is folded into the scores, otherwise it's kept for Deb's rules.
+    fn evaluate(&self, p: Vector, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) -> Evaluation {
+        let t = iteration as f64;
+        let mut scores = if self.ctx.is_multi_objective() {
+            self.ctx.evaluate_objectives_at(p.x, p.z, t)
+        } else {
+            vec![self.ctx.evaluate_at(p.x, p.z, t)]
+        };
+        for score in scores.iter_mut() {
+            *score = self.ctx.noise.measure(*score, self.resampling.samples, rng);
+        }
+
+        let mut violation = 0.0;
+        if !self.constraints.rules.is_empty() {
+            violation = self.constraints.violation(p, t, terrain);
+            if self.constraints.handling == ConstraintHandling::Penalty {
+                for (score, strategy) in scores.iter_mut().zip(self.ctx.get_strategies()) {
+                    *score = self.constraints.penalize(*score, violation, strategy);
+                }
+                violation = 0.0;
+            }
+        }
+
+        let score = scores[0];
+        if !self.ctx.is_multi_objective() {
+            scores.clear();
+        }
+        Evaluation {
+            score,
+            scores,
+            violation,
+        }
+    }
+
+    /// dominates compares (scores, violation) pairs of multi-objective problems,
+    /// a smaller violation wins and Pareto dominance decides between equally feasible ones
+    fn dominates(&self, a: (&[f64], f64), b: (&[f64], f64)) -> bool {
+        if a.1 != b.1 {
+            return a.1 < b.1;
+        }
+        dominates(&self.ctx.get_strategies(), a.0, b.0)
+    }
+
+    fn check_collision(&self, pos: Vector, terrain: &dyn HeightField) -> bool {
         let y = terrain.get_height(pos.x, pos.z);
         y >= pos.y 
     }
@@ -122,26 +377,74 @@ impl ParticleController{
     /// displaced by the magnitude of the velocity vector
     /// 
     /// note this model is completely arbitrary
-    fn get_optimal_collision_position(&self, state: &mut ParticleState, terrain: &Terrain) -> Vector {
+    fn get_optimal_collision_position(&self, state: &mut ParticleState, terrain: &dyn HeightField) -> Vector {
         let direction = self.sensor.find_clear_direction(state.p, state.v, terrain);
 
         state.p + (state.v.magnitude() * direction)
     }
 
-    fn calc_new_velocity(&self, g_best: Vector, params: &ParameterSet, state: &mut ParticleState, terrain: &Terrain) -> Vector {
-        let (w, c1, c2, c3) = (params.w, params.c1, params.c2, params.c3);
-        let (r1, r2, r3) = (gen_random(), gen_random(), gen_random());
+    /// fractional_memory weights the latest velocities by the Grünwald–Letnikov coefficients.
+    /// Velocities are the ones the particle took, the positions can't tell them apart
+    /// from collisions, which keep the particle in place, or boundary clipping.
+    fn fractional_memory(&self, params: &ParameterSet, state: &ParticleState) -> Vector {
+        gl_coefficients(params.alpha, params.fractional_order)
+            .into_iter()
+            .zip(state.velocities.iter().copied())
+            .fold(Vector::new(0.0, 0.0, 0.0), |acc, (coefficient, v)| acc + coefficient * v)
+    }
+
+    fn calc_new_velocity(&self, g_best: Vector, connectivity_target: Option<Vector>, params: &ParameterSet, state: &mut ParticleState, terrain: &dyn HeightField, rng: &mut Rng) -> Vector {
+        let (w, c1, c2, c3, c4) = (params.w, params.c1, params.c2, params.c3, params.c4);
+        let (r1, r2) = (rng.gen_random(), rng.gen_random());
         let (v, p, p_best) = (state.v, state.p, state.best_performance.position);
         let optimal_collision_pos = self.get_optimal_collision_position(state, terrain);
 
-        let v = w * v 
+        let memory = match params.velocity_mode {
+            VelocityMode::Inertia => w * v,
+            VelocityMode::Fractional => self.fractional_memory(params, state),
+        };
+
+        let mut v = memory
         + c1 * r1 * (p_best - p) 
         + c2 * r2 * (g_best - p)
         + c3 * (optimal_collision_pos - p);
 
+        if let Some(target) = connectivity_target {
+            v = v + c4 * rng.gen_random() * (target - p);
+        }
+
         if v.magnitude() > params.max_velocity {
             return params.max_velocity * v.unit();
         }
         v
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::goal::{Benchmark, Strategy};
+    use crate::pso::{ControllerConfig, SensorConfig};
+    use crate::space::Boundary;
+    use crate::terrain::surface::Plane;
+
+    #[test]
+    fn fractional_memory_survives_collisions() {
+        let mut rng = Rng::new(0);
+        let ground = Plane::new(0.0, Boundary::new(-100.0, 100.0, -100.0, 100.0));
+        let sensor = SensorConfig::new(10.0, 0.5, consts::PI / 6.0, consts::PI / 180.0);
+        let mut controller = ParticleController::new_from_config(Ctx::new(Benchmark::Sphere, Strategy::Minimize), ControllerConfig::new(sensor));
+        // only the memory moves the particle
+        let params = ParameterSet::new(0.5, 0.0, 0.0, 0.0, 0.0, 10.0).with_fractional_order(0.6, 3);
+
+        let v0 = Vector::new(2.0, -5.0, 0.0);
+        let mut state = ParticleState::new(0, Vector::new(0.0, 1.0, 0.0), v0, 4, &controller, &ground, &mut rng);
+        controller.update(&mut state, Vector::new(0.0, 0.0, 0.0), None, &params, 1, &ground, &mut rng);
+
+        // the particle stays in place, its memory holds the deflected velocity rather than a standstill
+        assert!(state.has_collided());
+        assert_eq!(state.get_position(), Vector::new(0.0, 1.0, 0.0));
+        let [c0, c1, _] = gl_coefficients(0.6, 3)[..] else { unreachable!() };
+        assert_eq!(controller.fractional_memory(&params, &state), c0 * state.get_velocity() + c1 * v0);
+    }
+}
@@ -1,9 +1,11 @@
 use crate::physics::collision::LinearDetector;
+use serde::{Serialize, Deserialize};
 use crate::space::Vector;
-use crate::terrain::Terrain;
+use crate::terrain::HeightField;
 
 #[derive(Debug, Copy, Clone)]
 /// Collision Sensor models a sensor which detects inbound collisions
+#[derive(Serialize, Deserialize)]
 pub struct CollisionSensor {
     /// detection_angle models the field of vision of the detector
     detection_angle: f64,
@@ -18,16 +20,16 @@ impl CollisionSensor {
 
     pub fn new(detection_angle: f64,  angular_step_size: f64, detector: LinearDetector) -> Self {
         Self {
-            detection_angle: detection_angle,
-            angular_step_size: angular_step_size,
-            detector: detector,
+            detection_angle,
+            angular_step_size,
+            detector,
         }
     }
 
 
     /// find_clear_direction returns a unit vector pointing to a direction with no obstacles in the Sensor FOV
     /// if no clear path is found, returns the leftmost direction seen by the sensor
-    pub fn find_clear_direction(&self, pos: Vector, direction: Vector, land: &Terrain) -> Vector {
+    pub fn find_clear_direction(&self, pos: Vector, direction: Vector, land: &dyn HeightField) -> Vector {
         let direction = direction.unit();
         let clear = self.bissect(pos, direction, self.detection_angle, land);
         if let Some(clear) = clear {
@@ -42,8 +44,8 @@ impl CollisionSensor {
     /// if there is rotate 1/4 of the FOV to the left and right and try again.
     /// recurses until a clear direction is found or the bissection angle becomes less than
     /// the step_size
-    fn bissect(&self, pos: Vector, direction: Vector, angle: f64, land: &Terrain) -> Option<Vector> {
-        let center_clear = self.detector.has_collision(pos, direction, land);
+    fn bissect(&self, pos: Vector, direction: Vector, angle: f64, land: &dyn HeightField) -> Option<Vector> {
+        let center_clear = !self.detector.has_collision(pos, direction, land);
         if center_clear {
             return Some(direction);
         }
@@ -63,4 +65,42 @@ impl CollisionSensor {
         let right = direction.rotate_xz(rotation_angle);
         self.bissect(pos, right, bissection, land)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::space::Boundary;
+    use crate::terrain::{Config, Terrain};
+    use crate::terrain::surface::{Cone, Plane};
+    use crate::utils::Rng;
+
+    #[test]
+    fn sensors_keep_clear_headings() {
+        let terrain = Terrain::new(Config::new_from_size(1000), &mut Rng::new(0)).unwrap();
+        let sensor = CollisionSensor::new(std::f64::consts::PI, 0.1, LinearDetector::new(50.0, 1.0));
+        let direction = Vector::new(1.0, 0.0, 0.0);
+
+        // far above the terrain nothing is in the way
+        assert_eq!(sensor.find_clear_direction(Vector::new(0.0, 1e6, 0.0), direction, &terrain), direction);
+
+        // underground every direction collides, the sensor falls back to its leftmost one
+        let leftmost = direction.rotate_xz(-std::f64::consts::PI / 2.0);
+        assert_eq!(sensor.find_clear_direction(Vector::new(0.0, -1e6, 0.0), direction, &terrain), leftmost);
+    }
+
+    #[test]
+    fn sensors_steer_around_obstacles() {
+        let boundary = Boundary::new(-100.0, 100.0, -100.0, 100.0);
+        let sensor = CollisionSensor::new(std::f64::consts::PI, std::f64::consts::PI / 180.0, LinearDetector::new(40.0, 0.5));
+        let (position, heading) = (Vector::new(0.0, 5.0, 0.0), Vector::unit_x());
+
+        assert_eq!(sensor.find_clear_direction(position, heading, &Plane::new(0.0, boundary)), heading);
+
+        // a peak straight ahead is avoided by turning a quarter of the field of view
+        let peak = Cone::new(20.0, 0.0, 0.0, 100.0, 10.0, boundary);
+        let clear = sensor.find_clear_direction(position, heading, &peak);
+        assert!(!sensor.detector.has_collision(position, clear, &peak));
+        assert!((clear.x - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-9, "{:?}", clear);
+    }
+}
@@ -1,21 +1,27 @@
 use std::f64::consts;
+use serde::{Serialize, Deserialize};
 
 use crate::wasm_bindgen;
+use crate::error::{Error, Result};
 
 use crate::space::Vector;
-use crate::goal::Performance;
+use crate::goal::{Performance, ParetoArchive};
 use super::ParameterSet;
 use super::Ctx;
 use super::particle::ParticleController;
 use super::particle::ParticleState;
-use crate::utils::gen_random;
+use crate::utils::Rng;
 use super::SwarmConfig;
+use super::DynamicConfig;
 use super::ParticleConfig;
-use crate::terrain::Terrain;
-use crate::space::Boundary;
+use super::CommConfig;
+use super::network::{self, Network};
+use super::topology::Topology;
+use crate::terrain::HeightField;
 
 #[derive(Debug)]
 #[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
 pub struct Swarm {
     ctx: Ctx,
     params: ParameterSet,
@@ -25,75 +31,33 @@ pub struct Swarm {
     historic_best: Performance,
     positions: Vec<Vector>,
     iteration: u64,
-    space_boundary: Boundary,
-}
-
-#[wasm_bindgen]
-impl Swarm {
-
-    pub fn new(ctx: Ctx,
-        params: ParameterSet, 
-        config: SwarmConfig,
-        controller: ParticleController,
-        particle_config: ParticleConfig,
-        terrain: &Terrain,
-    ) -> Self {
-        let size = config.size;
-
-        if size == 0 {
-            panic!("population size must be > 0")
-        }
-
-        let unit = Vector::unit_x();
-
-        let mut population = Vec::with_capacity(size);
-        let mut positions = Vec::with_capacity(size);
 
-        // initialize particles
-        for i in 0..size {
-            let rotation_angle = consts::TAU * gen_random();
-            let magnitude = config.deploy_spread_radius * gen_random();
+    /// comm models the communication constraints between the swarm's particles
+    comm: CommConfig,
 
-            let rotation_vec = unit.rotate_xz(rotation_angle);
-            let spread = magnitude * rotation_vec;
-            let start_position = config.deploy_position + spread;
+    /// network is the connectivity graph computed at the start of the latest update,
+    /// it's rebuilt whenever the members change so that its indexes match the population
+    network: Network,
 
-            let v0 = config.initial_swarm_velocity * rotation_vec;
+    /// topology defines which particles inform each other
+    topology: Topology,
 
-            population.push(ParticleState::new(start_position, v0, particle_config.position_log_size, &controller));
-        }
+    /// stagnation counts the iterations since the historic best last improved
+    stagnation: usize,
 
-        // initialize position vectors
-        for i in 0..size {
-            positions.push(Vector::new(0.0, 0.0, 0.0));
-        }
-        let initial_perf = population[0].get_performance();
+    /// improved is set if the latest update improved the historic best
+    improved: bool,
 
-        let mut swarm = Self {
-            params: params,
-            population: population,
-            controller: controller,
-            positions: positions,
-            historic_best: initial_perf.clone(),
-            best: initial_perf.clone(),
-            ctx: ctx,
-            iteration: 0,
-            space_boundary: terrain.get_boundary(),
-        };
+    /// kills counts how many particles were excluded from the swarm as punishment
+    kills: usize,
 
-        swarm.update_positions();
-        swarm.update_bests();
-        swarm
-    }
+    /// historic_samples counts the measurements averaged into the historic best's score
+    #[serde(default)]
+    historic_samples: usize,
+}
 
-    pub fn update(&mut self, terrain: &Terrain) {
-        for particle in self.population.iter_mut() {
-            self.controller.update(particle, self.best.position, &self.params, &self.space_boundary, terrain);
-        }
-        self.update_positions();
-        self.update_bests();
-        self.iteration += 1;
-    }
+#[wasm_bindgen]
+impl Swarm {
 
     fn update_positions(&mut self) {
         for (i, particle) in self.population.iter().enumerate() {
@@ -102,12 +66,26 @@ impl Swarm {
     }
 
     /// update_bests finds the new current best performance,
-    /// sets it as bests and updates the historic best if necessary
-    fn update_bests(&mut self) {
+    /// sets it as bests and updates the historic best if necessary.
+    /// Multi-objective swarms improve by adding solutions to the Pareto archive instead.
+    fn update_bests(&mut self, archive: Option<&mut ParetoArchive>) {
         self.best = self.find_best();
 
-        let historic_best = self.ctx.get_strategy().pick_best_performance(&self.best, &self.historic_best);
-        self.historic_best = historic_best;
+        let improves_best = self.ctx.get_strategy().is_better(&self.best, &self.historic_best);
+        if improves_best {
+            self.historic_best = self.best;
+            self.historic_samples = self.controller.get_resampling().samples;
+        }
+
+        self.improved = match archive {
+            Some(archive) => self.population.iter().filter(|p| p.get_violation() == 0.0 && archive.insert(p.get_pareto_point())).count() > 0,
+            None => improves_best,
+        };
+        if self.improved {
+            self.stagnation = 0;
+        } else {
+            self.stagnation += 1;
+        }
     }
 
     /// find_best returns the score of the currently best performing particle
@@ -137,28 +115,235 @@ impl Swarm {
         self.positions.len()
     }
 
+    /// get_stagnation returns for how many iterations the swarm hasn't improved its historic best
+    pub fn get_stagnation(&self) -> usize {
+        self.stagnation
+    }
+
+    /// has_improved reports whether the last update improved the swarm's historic best
+    pub fn has_improved(&self) -> bool {
+        self.improved
+    }
+
+    /// get_collision_count returns the amount of collisions detected by the swarm's members
+    pub fn get_collision_count(&self) -> usize {
+        self.population.iter().map(|p| p.get_collisions()).sum()
+    }
+
+    /// get_member_ids returns the ids of the particles which belong to the swarm
+    pub fn get_member_ids(&self) -> Vec<usize> {
+        self.population.iter().map(|p| p.get_id()).collect()
+    }
+
     /// get_position_by_idx returns a pointer to the idxth particle from
     /// the contiguous memory of the underlying Vec.
     /// 
     /// Note this method is primarily used for the WASM wrapper in order
     /// to directly access the Vector with a copy.
-    pub fn get_position_ptr_by_idx(&self, idx: usize) -> *const Vector {
-        if idx >= self.positions.len() {
-            panic!("idx must be less than {}", self.positions.len());
-        }
-        self.positions.get(idx).unwrap() as *const Vector
+    pub fn get_position_ptr_by_idx(&self, idx: usize) -> Result<*const Vector> {
+        let idx = Error::check_index(idx, self.positions.len())?;
+        Ok(&self.positions[idx] as *const Vector)
     }
 
-    pub fn get_position_by_idx(&self, idx: usize) -> Vector {
-        if idx >= self.positions.len() {
-            panic!("idx must be less than {}", self.positions.len());
-        }
-        self.positions[idx]
+    pub fn get_position_by_idx(&self, idx: usize) -> Result<Vector> {
+        let idx = Error::check_index(idx, self.positions.len())?;
+        Ok(self.positions[idx])
     }
 }
 
 impl Swarm {
+
+    pub fn new(ctx: Ctx,
+        params: ParameterSet, 
+        config: SwarmConfig,
+        controller: ParticleController,
+        particle_config: ParticleConfig,
+        terrain: &dyn HeightField,
+        rng: &mut Rng,
+    ) -> Result<Self> {
+        let population = deploy(&config, &particle_config, &controller, terrain, rng)?;
+        Self::from_population(ctx, params, controller, population, &config, terrain, rng)
+    }
+
+    /// update moves every particle, each one is guided by the best performance
+    /// of its neighbourhood that it can reach through the communication network.
+    /// iteration is the simulation's iteration count, swarms spawned mid run take it over.
+    /// For multi-objective problems the particles are guided by leaders picked from the archive.
+    pub fn update(&mut self, iteration: u64, archive: Option<&mut ParetoArchive>, terrain: &dyn HeightField, rng: &mut Rng) {
+        self.iteration = iteration;
+        if self.topology.get_size() != self.population.len() {
+            self.topology.resize(self.population.len(), rng);
+        } else {
+            self.topology.on_stagnation(self.stagnation, rng);
+        }
+        self.network = Network::build(&self.positions, &self.comm, terrain);
+
+        let performances: Vec<Performance> = self.population.iter().map(|p| p.get_performance()).collect();
+        let social_bests = self.topology.neighbourhood_bests(&performances, &self.network, self.ctx.get_strategy());
+
+        for (i, particle) in self.population.iter_mut().enumerate() {
+            let target = network::connectivity_target(i, &self.positions, &self.comm);
+            let leader = archive.as_deref()
+                .and_then(|archive| archive.select_leader(rng))
+                .map_or(social_bests[i].position, |leader| leader.position);
+            self.controller.update(particle, leader, target, &self.params, self.iteration + 1, terrain, rng);
+        }
+        self.update_positions();
+        self.update_bests(archive);
+        self.iteration += 1;
+    }
+
+    /// respond_to_change applies the configured responses to a change of the goal,
+    /// the swarm's bests are reset since they refer to the previous landscape
+    pub fn respond_to_change(&mut self, config: &DynamicConfig, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) {
+        for particle in self.population.iter_mut() {
+            if config.randomize_fraction > 0.0 && rng.gen_random() < config.randomize_fraction {
+                self.controller.relocate(particle, iteration, terrain, rng);
+            } else {
+                self.controller.respond_to_change(particle, config, iteration, terrain, rng);
+            }
+        }
+
+        self.update_positions();
+        self.best = self.find_best();
+        self.historic_best = self.best;
+        self.historic_samples = self.controller.get_resampling().samples;
+        self.improved = false;
+    }
+
+    /// remeasure_bests measures the personal bests and the historic best again,
+    /// so that lucky measurements of noisy goals don't stick forever
+    pub fn remeasure_bests(&mut self, iteration: u64, terrain: &dyn HeightField, rng: &mut Rng) {
+        for particle in self.population.iter_mut() {
+            self.controller.remeasure_best(particle, iteration, terrain, rng);
+        }
+        self.controller.remeasure(&mut self.historic_best, &mut self.historic_samples, iteration, terrain, rng);
+    }
+
+    /// from_population builds a swarm out of already deployed particles
+    pub fn from_population(
+        ctx: Ctx,
+        params: ParameterSet,
+        controller: ParticleController,
+        population: Vec<ParticleState>,
+        config: &SwarmConfig,
+        terrain: &dyn HeightField,
+        rng: &mut Rng,
+    ) -> Result<Self> {
+        if population.is_empty() {
+            return Err(Error::EmptyPopulation);
+        }
+
+        let positions = vec![Vector::new(0.0, 0.0, 0.0); population.len()];
+        let initial_perf = population[0].get_performance();
+
+        let mut swarm = Self {
+            params,
+            population,
+            controller,
+            topology: Topology::new(config.topology, positions.len(), rng),
+            positions,
+            historic_best: initial_perf,
+            best: initial_perf,
+            ctx,
+            iteration: 0,
+            comm: config.comm,
+            network: Network::default(),
+            stagnation: 0,
+            improved: false,
+            kills: 0,
+            historic_samples: 0,
+        };
+        swarm.historic_samples = swarm.controller.get_resampling().samples;
+
+        swarm.update_positions();
+        swarm.network = Network::build(&swarm.positions, &swarm.comm, terrain);
+        swarm.update_bests(None);
+        swarm.stagnation = 0;
+        swarm.improved = false;
+        Ok(swarm)
+    }
+
     pub fn get_positions(&self) -> &Vec<Vector> {
         &self.positions
     }
+
+    pub fn get_network(&self) -> &Network {
+        &self.network
+    }
+
+    pub fn get_population(&self) -> &[ParticleState] {
+        &self.population
+    }
+
+    /// add_particle recruits a particle into the swarm
+    pub fn add_particle(&mut self, particle: ParticleState, terrain: &dyn HeightField) {
+        self.positions.push(particle.get_position());
+        self.population.push(particle);
+        self.network = Network::build(&self.positions, &self.comm, terrain);
+    }
+
+    /// exclude_worst removes the worst performing particle from the swarm
+    /// and resets the stagnation counter according to the number of exclusions
+    /// the swarm has suffered, as in SC = SC_max * (1 - 1 / (N_kill + 1)).
+    ///
+    /// Returns None if the swarm only has a single particle left.
+    pub fn exclude_worst(&mut self, stagnation_threshold: usize, terrain: &dyn HeightField) -> Option<ParticleState> {
+        if self.population.len() <= 1 {
+            return None;
+        }
+
+        let strategy = self.ctx.get_strategy();
+        let mut worst = 0;
+        for (i, particle) in self.population.iter().enumerate().skip(1) {
+            if strategy.is_better(&self.population[worst].get_performance(), &particle.get_performance()) {
+                worst = i;
+            }
+        }
+
+        let particle = self.population.swap_remove(worst);
+        self.positions.swap_remove(worst);
+        self.network = Network::build(&self.positions, &self.comm, terrain);
+
+        self.kills += 1;
+        let threshold = stagnation_threshold as f64;
+        self.stagnation = (threshold * (1.0 - 1.0 / (self.kills as f64 + 1.0))) as usize;
+        self.best = self.find_best();
+
+        Some(particle)
+    }
+
+    /// disband dissolves the swarm, returning all of its particles
+    pub fn disband(self) -> Vec<ParticleState> {
+        self.population
+    }
+}
+
+/// deploy spreads config.size particles randomly around the deploy position
+pub fn deploy(config: &SwarmConfig, particle_config: &ParticleConfig, controller: &ParticleController, terrain: &dyn HeightField, rng: &mut Rng) -> Result<Vec<ParticleState>> {
+    let size = config.size;
+
+    if size == 0 {
+        return Err(Error::EmptyPopulation);
+    }
+
+    let unit = Vector::unit_x();
+
+    let mut population = Vec::with_capacity(size);
+
+    // initialize particles
+    for id in 0..size {
+        let rotation_angle = consts::TAU * rng.gen_random();
+        let magnitude = config.deploy_spread_radius * rng.gen_random();
+
+        let rotation_vec = unit.rotate_xz(rotation_angle);
+        let spread = magnitude * rotation_vec;
+        let start_position = config.deploy_position + spread;
+
+        let v0 = config.initial_swarm_velocity * rotation_vec;
+
+        population.push(ParticleState::new(id, start_position, v0, particle_config.position_log_size, controller, terrain, rng));
+    }
+
+    Ok(population)
 }
\ No newline at end of file
@@ -0,0 +1,146 @@
+use crate::goal::{Performance, Strategy};
+use serde::{Serialize, Deserialize};
+use crate::utils::Rng;
+use super::network::Network;
+use super::{TopologyConfig, TopologyKind};
+
+/// Topology defines which particles inform each other of their performance,
+/// the social term of each particle is the best performance among its informants.
+#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize)]
+pub struct Topology {
+    config: TopologyConfig,
+
+    /// informants lists, for every particle index, the indexes of the particles that inform it.
+    /// Star topologies don't store informants, everyone informs everyone.
+    informants: Vec<Vec<usize>>,
+
+    size: usize,
+}
+
+impl Topology {
+
+    pub fn new(config: TopologyConfig, size: usize, rng: &mut Rng) -> Self {
+        let mut topology = Self {
+            config,
+            informants: Vec::new(),
+            size,
+        };
+        topology.build(rng);
+        topology
+    }
+
+    /// resize rebuilds the topology for a population of the given size
+    pub fn resize(&mut self, size: usize, rng: &mut Rng) {
+        self.size = size;
+        self.build(rng);
+    }
+
+    /// on_stagnation is called with the swarm's stagnation counter after every update,
+    /// random topologies are re-drawn once per stagnation event, ie when the swarm first fails to improve
+    pub fn on_stagnation(&mut self, stagnation: usize, rng: &mut Rng) {
+        if self.config.kind == TopologyKind::Random && stagnation == 1 {
+            self.build(rng);
+        }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    fn build(&mut self, rng: &mut Rng) {
+        let size = self.size;
+        let k = self.config.neighbours;
+
+        self.informants = match self.config.kind {
+            TopologyKind::Star => Vec::new(),
+            TopologyKind::Ring => (0..size)
+                .map(|i| {
+                    let mut informants = Vec::with_capacity(2 * k);
+                    for offset in 1..=k.min(size / 2) {
+                        informants.push((i + offset) % size);
+                        informants.push((i + size - offset) % size);
+                    }
+                    informants
+                })
+                .collect(),
+            TopologyKind::VonNeumann => {
+                let cols = (size as f64).sqrt().ceil().max(1.0) as usize;
+                let rows = size.div_ceil(cols);
+                (0..size)
+                    .map(|i| {
+                        let (row, col) = (i / cols, i % cols);
+                        let candidates = [
+                            ((row + rows - 1) % rows) * cols + col,
+                            ((row + 1) % rows) * cols + col,
+                            row * cols + (col + cols - 1) % cols,
+                            row * cols + (col + 1) % cols,
+                        ];
+                        candidates.iter().copied().filter(|&j| j < size && j != i).collect()
+                    })
+                    .collect()
+            },
+            TopologyKind::Random => {
+                // each particle informs k random particles, as in SPSO 2007
+                let mut informants = vec![Vec::new(); size];
+                for i in 0..size {
+                    for _ in 0..k {
+                        let j = rng.gen_random_range(0, size);
+                        if j != i && !informants[j].contains(&i) {
+                            informants[j].push(i);
+                        }
+                    }
+                }
+                informants
+            },
+        };
+    }
+
+    /// neighbourhood_bests returns, for every particle, the best performance among
+    /// itself and its informants which it can reach through the communication network
+    pub fn neighbourhood_bests(&self, performances: &[Performance], network: &Network, strategy: Strategy) -> Vec<Performance> {
+        if self.config.kind == TopologyKind::Star {
+            return network.reachable_bests(performances, strategy);
+        }
+
+        self.informants.iter()
+            .enumerate()
+            .map(|(i, informants)| {
+                informants.iter()
+                    .filter(|&&j| network.get_component(j) == network.get_component(i))
+                    .fold(performances[i], |best, &j| strategy.pick_best_performance(&best, &performances[j]))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_and_von_neumann_informants() {
+        let ring = Topology::new(TopologyConfig::new(TopologyKind::Ring, 1), 5, &mut Rng::new(0));
+        assert_eq!(ring.informants[0], vec![1, 4]);
+
+        let grid = Topology::new(TopologyConfig::new(TopologyKind::VonNeumann, 0), 9, &mut Rng::new(0));
+        let mut informants = grid.informants[4].clone();
+        informants.sort();
+        assert_eq!(informants, vec![1, 3, 5, 7]);
+    }
+
+    #[test]
+    fn random_informants_are_redrawn_once_per_stagnation() {
+        let mut rng = Rng::new(0);
+        let mut random = Topology::new(TopologyConfig::new(TopologyKind::Random, 3), 20, &mut rng);
+        let mut redraws = 0;
+        for stagnation in [0, 1, 2, 3, 4, 0, 0, 1, 2, 3] {
+            let informants = random.informants.clone();
+            random.on_stagnation(stagnation, &mut rng);
+            if random.informants != informants {
+                redraws += 1;
+            }
+        }
+        assert_eq!(redraws, 2);
+    }
+}
@@ -0,0 +1,413 @@
+//! recorder captures the per iteration state of a simulation,
+//! recordings can be replayed through `Simulator::from_recording`
+
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::goal::Performance;
+use crate::pso::SubswarmManager;
+use crate::space::Vector;
+use crate::Simulator;
+use crate::error::{Error, Result};
+
+/// MAGIC prefixes binary recordings
+const MAGIC: &[u8; 8] = b"RDPSOREC";
+const VERSION: u32 = 2;
+
+/// ParticleFrame is the state of a single particle at a given iteration
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ParticleFrame {
+    pub id: usize,
+    pub position: Vector,
+    pub velocity: Vector,
+    pub score: f64,
+    pub best: Performance,
+
+    /// collided reports whether the terrain blocked the particle during the iteration
+    pub collided: bool,
+}
+
+/// Frame is the state of the whole simulation at a given iteration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub iteration: u64,
+    pub best: Performance,
+    pub historic_best: Performance,
+
+    /// particles are sorted by id
+    pub particles: Vec<ParticleFrame>,
+}
+
+impl Frame {
+
+    /// capture copies the current state of the swarms
+    pub fn capture(swarm: &SubswarmManager) -> Self {
+        let mut particles: Vec<ParticleFrame> = swarm.get_particles()
+            .map(|p| ParticleFrame {
+                id: p.get_id(),
+                position: p.get_position(),
+                velocity: p.get_velocity(),
+                score: p.get_score(),
+                best: p.get_best_performance(),
+                collided: p.has_collided(),
+            })
+            .collect();
+        particles.sort_by_key(|p| p.id);
+
+        Self {
+            iteration: swarm.get_iteration(),
+            best: swarm.get_best(),
+            historic_best: swarm.get_historic_best(),
+            particles,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordingFormat {
+    /// JsonLines writes a snapshot of the simulation in the first line followed by one frame per line
+    JsonLines,
+
+    /// Binary writes little endian numbers, see Recorder::write_binary_frame for the layout
+    Binary,
+}
+
+impl RecordingFormat {
+    /// from_path picks the binary format for `.bin` files and JSON Lines otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("bin") => Self::Binary,
+            _ => Self::JsonLines,
+        }
+    }
+}
+
+/// Recorder streams frames to a writer
+pub struct Recorder<W: Write> {
+    out: W,
+    format: RecordingFormat,
+}
+
+impl<W: Write> Recorder<W> {
+
+    /// new writes the recording header, a snapshot of the simulation as it is when the recording starts.
+    /// It holds the terrain and the goal themselves, so that playbacks don't depend on the files they were read from.
+    pub fn new(mut out: W, format: RecordingFormat, simulator: &Simulator) -> Result<Self> {
+        let snapshot = simulator.snapshot()?;
+        match format {
+            RecordingFormat::JsonLines => writeln!(out, "{}", snapshot),
+            RecordingFormat::Binary => out.write_all(MAGIC)
+                .and_then(|_| out.write_all(&VERSION.to_le_bytes()))
+                .and_then(|_| out.write_all(&(snapshot.len() as u32).to_le_bytes()))
+                .and_then(|_| out.write_all(snapshot.as_bytes())),
+        }.map_err(|err| Error::Io(err.to_string()))?;
+
+        Ok(Self {
+            out,
+            format,
+        })
+    }
+
+    pub fn record(&mut self, frame: &Frame) -> Result<()> {
+        match self.format {
+            RecordingFormat::JsonLines => {
+                let line = serde_json::to_string(frame).map_err(|err| Error::Serialization(err.to_string()))?;
+                writeln!(self.out, "{}", line).map_err(|err| Error::Io(err.to_string()))
+            },
+            RecordingFormat::Binary => {
+                let mut buf = Vec::with_capacity(80 + frame.particles.len() * 101);
+                Self::write_binary_frame(&mut buf, frame);
+                self.out.write_all(&buf).map_err(|err| Error::Io(err.to_string()))
+            },
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush().map_err(|err| Error::Io(err.to_string()))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// write_binary_frame encodes a frame as
+    /// iteration: u64, best and historic_best: 4 f64 each, particle count: u32, then for every particle
+    /// id: u32, position and velocity: 3 f64 each, score: f64, best: 4 f64, collided: u8
+    fn write_binary_frame(buf: &mut Vec<u8>, frame: &Frame) {
+        buf.extend(frame.iteration.to_le_bytes());
+        write_performance(buf, &frame.best);
+        write_performance(buf, &frame.historic_best);
+        buf.extend((frame.particles.len() as u32).to_le_bytes());
+        for particle in frame.particles.iter() {
+            buf.extend((particle.id as u32).to_le_bytes());
+            write_vector(buf, &particle.position);
+            write_vector(buf, &particle.velocity);
+            buf.extend(particle.score.to_le_bytes());
+            write_performance(buf, &particle.best);
+            buf.push(particle.collided as u8);
+        }
+    }
+}
+
+fn write_vector(buf: &mut Vec<u8>, v: &Vector) {
+    for component in [v.x, v.y, v.z] {
+        buf.extend(component.to_le_bytes());
+    }
+}
+
+fn write_performance(buf: &mut Vec<u8>, performance: &Performance) {
+    buf.extend(performance.score.to_le_bytes());
+    write_vector(buf, &performance.position);
+}
+
+/// Recording is a fully loaded recording
+#[derive(Debug)]
+pub struct Recording {
+    /// initial is the simulation as it was when the recording started
+    pub initial: Simulator,
+    pub frames: Vec<Frame>,
+}
+
+impl Recording {
+
+    /// parse decodes a recording in either format, binary recordings are detected by their prefix
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.starts_with(MAGIC) {
+            Self::parse_binary(data)
+        } else {
+            let text = std::str::from_utf8(data).map_err(|err| Error::Parse(format!("invalid recording: {}", err)))?;
+            Self::parse_json_lines(text)
+        }
+    }
+
+    fn parse_json_lines(text: &str) -> Result<Self> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next().ok_or_else(|| Error::Parse("empty recording".to_string()))?;
+        let initial = Simulator::restore(header)?;
+        let frames = lines
+            .enumerate()
+            .map(|(i, line)| serde_json::from_str(line).map_err(|err| Error::Parse(format!("invalid frame {}: {}", i, err))))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            initial,
+            frames,
+        })
+    }
+
+    fn parse_binary(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data, offset: MAGIC.len() };
+        let version = reader.read_u32()?;
+        if version != VERSION {
+            return Err(Error::Parse(format!("unsupported recording version {}", version)));
+        }
+
+        let header_len = reader.read_u32()? as usize;
+        let header = std::str::from_utf8(reader.take(header_len)?).map_err(|err| Error::Parse(format!("invalid recording header: {}", err)))?;
+        let initial = Simulator::restore(header)?;
+
+        let mut frames = Vec::new();
+        while !reader.is_empty() {
+            frames.push(reader.read_frame()?);
+        }
+
+        Ok(Self {
+            initial,
+            frames,
+        })
+    }
+}
+
+/// Reader decodes the little endian numbers of binary recordings
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+
+    fn is_empty(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.offset + len;
+        if end > self.data.len() {
+            return Err(Error::Parse(format!("truncated recording at byte {}", self.offset)));
+        }
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        self.read_array().map(u32::from_le_bytes)
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        self.read_array().map(u64::from_le_bytes)
+    }
+
+    fn read_f64(&mut self) -> Result<f64> {
+        self.read_array().map(f64::from_le_bytes)
+    }
+
+    fn read_vector(&mut self) -> Result<Vector> {
+        Ok(Vector::new(self.read_f64()?, self.read_f64()?, self.read_f64()?))
+    }
+
+    fn read_performance(&mut self) -> Result<Performance> {
+        let score = self.read_f64()?;
+        Ok(Performance::new(self.read_vector()?, score))
+    }
+
+    fn read_frame(&mut self) -> Result<Frame> {
+        let iteration = self.read_u64()?;
+        let best = self.read_performance()?;
+        let historic_best = self.read_performance()?;
+        let count = self.read_u32()? as usize;
+
+        let mut particles = Vec::with_capacity(count);
+        for _ in 0..count {
+            particles.push(ParticleFrame {
+                id: self.read_u32()? as usize,
+                position: self.read_vector()?,
+                velocity: self.read_vector()?,
+                score: self.read_f64()?,
+                best: self.read_performance()?,
+                collided: self.read_array::<1>()?[0] != 0,
+            });
+        }
+
+        Ok(Frame {
+            iteration,
+            best,
+            historic_best,
+            particles,
+        })
+    }
+}
+
+/// Playback steps through the frames of a recording
+#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize)]
+pub struct Playback {
+    frames: Vec<Frame>,
+    cursor: usize,
+}
+
+impl Playback {
+
+    pub fn new(frames: Vec<Frame>) -> Result<Self> {
+        if frames.is_empty() {
+            return Err(Error::Parse("recording has no frames".to_string()));
+        }
+        Ok(Self {
+            frames,
+            cursor: 0,
+        })
+    }
+
+    /// step advances to the next frame, the last frame is kept once the recording ends
+    pub fn step(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.frames.len() - 1);
+    }
+
+    pub fn seek(&mut self, idx: usize) {
+        self.cursor = idx.min(self.frames.len() - 1);
+    }
+
+    pub fn get_cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn get_frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn get_frame(&self) -> &Frame {
+        &self.frames[self.cursor]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::goal::{Benchmark, Strategy};
+    use crate::{pso, terrain, SimConfig};
+
+    #[test]
+    fn recordings_round_trip() {
+        let sensor = pso::SensorConfig::new(10.0, 0.5, std::f64::consts::PI / 6.0, std::f64::consts::PI / 180.0);
+        let config = SimConfig::new(
+            pso::ParameterSet::new(0.5, 2.0, 2.0, 0.5, 0.5, 10.0),
+            terrain::Config::new_from_size(1000),
+            pso::ControllerConfig::new(sensor),
+            pso::SwarmConfig::new(6, Vector::new(10.0, 300.0, 10.0), 10.0, 0.5, pso::CommConfig::unconstrained(), pso::TopologyConfig::default()),
+            pso::DarwinConfig::new(2, 1, 3, 2, 4, 5),
+            pso::Ctx::new(Benchmark::Ackley, Strategy::Minimize),
+            pso::ParticleConfig::new(5),
+            3,
+        );
+
+        let mut simulator = Simulator::new(config.clone()).unwrap();
+        let mut frames = vec![Frame::capture(simulator.get_swarm())];
+        for _ in 0..5 {
+            simulator.step();
+            frames.push(Frame::capture(simulator.get_swarm()));
+        }
+
+        for format in [RecordingFormat::JsonLines, RecordingFormat::Binary] {
+            let mut recorder = Recorder::new(Vec::new(), format, &Simulator::new(config.clone()).unwrap()).unwrap();
+            for frame in frames.iter() {
+                recorder.record(frame).unwrap();
+            }
+
+            let data = recorder.into_inner();
+            let recording = Recording::parse(&data).unwrap();
+            assert_eq!(recording.frames, frames);
+            assert_eq!(recording.initial.get_config().seed, config.seed);
+
+            let mut playback = Simulator::from_recording(&data).unwrap();
+            for _ in 0..3 {
+                playback.step();
+            }
+            assert_eq!(playback.get_particle_position_by_idx(2), Ok(frames[3].particles[2].position));
+        }
+    }
+
+    #[test]
+    fn recordings_play_without_their_files() {
+        let path = std::env::temp_dir().join("rdpso_recorded_heightmap.pgm");
+        let mut pgm = b"P5 4 3 255\n".to_vec();
+        pgm.extend_from_slice(&[0, 10, 20, 30, 10, 20, 30, 40, 20, 30, 40, 50]);
+        std::fs::write(&path, &pgm).unwrap();
+
+        let mut config = crate::tests::config(4);
+        config.terrain.heightmap = Some(terrain::HeightmapSpec {
+            path: path.to_string_lossy().to_string(),
+            format: None,
+            width: 0,
+            vertical_scale: 2.0,
+            cell_size: Some(100.0),
+            origin: None,
+        });
+        let simulator = Simulator::new(config).unwrap();
+
+        // recordings carry the heightmap, they play once the file is gone
+        let mut recorder = Recorder::new(Vec::new(), RecordingFormat::Binary, &simulator).unwrap();
+        recorder.record(&simulator.capture_frame()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut playback = Simulator::from_recording(&recorder.into_inner()).unwrap();
+        assert_eq!(playback.terrain.get_height(-100.0, 50.0), 20.0);
+        assert!(playback.reset().is_ok());
+    }
+}
@@ -0,0 +1,299 @@
+//! runner drives headless simulations, it backs the command line interface
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::goal::{Performance, Strategy};
+use crate::{SimConfig, Simulator};
+
+/// load_config reads a SimConfig from a JSON or TOML file, applying the given
+/// `path.to.field=value` overrides on top of it, the result is validated
+pub fn load_config(path: &Path, overrides: &[String]) -> Result<SimConfig> {
+    let mut value = read_sim_config_value(path)?;
+    for assignment in overrides {
+        apply_override(&mut value, assignment)?;
+    }
+    let config: SimConfig = serde_json::from_value(value).map_err(|err| Error::Parse(format!("invalid config: {}", err)))?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// read_config_value reads a config file into a generic JSON value,
+/// the format is picked from the file extension
+pub fn read_config_value(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path).map_err(|err| Error::Io(format!("can't read {}: {}", path.display(), err)))?;
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => {
+            let value: toml::Value = toml::from_str(&content).map_err(|err| Error::Parse(format!("invalid toml: {}", err)))?;
+            serde_json::to_value(value).map_err(|err| Error::Parse(format!("invalid toml: {}", err)))
+        },
+        _ => serde_json::from_str(&content).map_err(|err| Error::Parse(format!("invalid json: {}", err))),
+    }
+}
+
+/// read_sim_config_value reads a SimConfig file into a generic JSON value holding every field,
+/// the ones the file leaves to their defaults included, so that overrides can address them
+pub fn read_sim_config_value(path: &Path) -> Result<Value> {
+    let config: SimConfig = serde_json::from_value(read_config_value(path)?).map_err(|err| Error::Parse(format!("invalid config: {}", err)))?;
+    serde_json::to_value(config).map_err(|err| Error::Serialization(format!("invalid config: {}", err)))
+}
+
+/// apply_override sets the field addressed by a dotted path, ie `swarm.size=20`.
+/// The value is parsed as JSON and falls back to a plain string, so `ctx.goal=Ackley` works.
+pub fn apply_override(config: &mut Value, assignment: &str) -> Result<()> {
+    let (path, raw) = assignment
+        .split_once('=')
+        .ok_or_else(|| Error::Parse(format!("invalid override {}, expected path=value", assignment)))?;
+    let value = serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+    set_path(config, path, value)
+}
+
+/// set_path replaces the field addressed by a dotted path with value
+pub fn set_path(config: &mut Value, path: &str, value: Value) -> Result<()> {
+    let mut field = config;
+    for key in path.split('.') {
+        field = field
+            .get_mut(key)
+            .ok_or_else(|| Error::invalid_config(path, "unknown field"))?;
+    }
+    *field = value;
+    Ok(())
+}
+
+/// save_snapshot writes the simulation state, as JSON for .json files and as binary otherwise
+pub fn save_snapshot(simulator: &Simulator, path: &Path) -> Result<()> {
+    let data = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => simulator.snapshot()?.into_bytes(),
+        _ => simulator.snapshot_bytes()?,
+    };
+    fs::write(path, data).map_err(|err| Error::Io(format!("can't write {}: {}", path.display(), err)))
+}
+
+/// load_snapshot restores a simulation saved by save_snapshot
+pub fn load_snapshot(path: &Path) -> Result<Simulator> {
+    let data = fs::read(path).map_err(|err| Error::Io(format!("can't read {}: {}", path.display(), err)))?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Simulator::restore(std::str::from_utf8(&data).map_err(|err| Error::Parse(err.to_string()))?),
+        _ => Simulator::restore_bytes(&data),
+    }
+}
+
+/// StopCondition determines when a run ends, the run stops as soon as any of the conditions is met
+#[derive(Debug, Clone, Copy)]
+pub struct StopCondition {
+    /// max_iterations is the upper bound of steps
+    pub max_iterations: u64,
+
+    /// target_score stops the run once the historic best reaches it
+    pub target_score: Option<f64>,
+
+    /// stagnation stops the run after the given amount of steps without improvement
+    pub stagnation: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum StopReason {
+    MaxIterations,
+    TargetReached,
+    Stagnation,
+}
+
+/// RunSummary describes how a run ended
+#[derive(Debug, Clone, Copy)]
+pub struct RunSummary {
+    pub iterations: u64,
+    pub reason: StopReason,
+    pub historic_best: Performance,
+
+    /// converged_at is the iteration in which the historic best was last improved
+    pub converged_at: u64,
+}
+
+/// IterationRecord is the per iteration output of a run
+#[derive(Debug, Clone, Serialize)]
+pub struct IterationRecord {
+    pub iteration: u64,
+    pub best_score: f64,
+    pub best_x: f64,
+    pub best_y: f64,
+    pub best_z: f64,
+    pub historic_best_score: f64,
+    pub historic_best_x: f64,
+    pub historic_best_y: f64,
+    pub historic_best_z: f64,
+    pub swarm_count: usize,
+    pub excluded_count: usize,
+    pub collisions: usize,
+}
+
+impl IterationRecord {
+    pub fn new(simulator: &Simulator) -> Self {
+        let swarm = simulator.get_swarm();
+        let (best, historic_best) = (swarm.get_best(), swarm.get_historic_best());
+        Self {
+            iteration: swarm.get_iteration(),
+            best_score: best.score,
+            best_x: best.position.x,
+            best_y: best.position.y,
+            best_z: best.position.z,
+            historic_best_score: historic_best.score,
+            historic_best_x: historic_best.position.x,
+            historic_best_y: historic_best.position.y,
+            historic_best_z: historic_best.position.z,
+            swarm_count: swarm.get_swarm_count(),
+            excluded_count: swarm.get_excluded().len(),
+            collisions: swarm.get_collision_count(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Csv,
+    JsonLines,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::JsonLines),
+            _ => Err(Error::Parse(format!("unknown format {}, expected csv or jsonl", s))),
+        }
+    }
+}
+
+/// RecordWriter writes iteration records as CSV or JSON Lines
+pub struct RecordWriter {
+    out: Box<dyn Write>,
+    format: OutputFormat,
+    header_written: bool,
+}
+
+impl RecordWriter {
+    pub fn new(out: Box<dyn Write>, format: OutputFormat) -> Self {
+        Self {
+            out,
+            format,
+            header_written: false,
+        }
+    }
+
+    pub fn write(&mut self, record: &IterationRecord) -> Result<()> {
+        let line = match self.format {
+            OutputFormat::JsonLines => serde_json::to_string(record).map_err(|err| Error::Serialization(err.to_string()))?,
+            OutputFormat::Csv => {
+                let Value::Object(fields) = serde_json::to_value(record).map_err(|err| Error::Serialization(err.to_string()))? else {
+                    return Err(Error::Serialization("records must serialize to objects".to_string()));
+                };
+                if !self.header_written {
+                    let header: Vec<&str> = fields.keys().map(|key| key.as_str()).collect();
+                    writeln!(self.out, "{}", header.join(",")).map_err(|err| Error::Io(err.to_string()))?;
+                    self.header_written = true;
+                }
+                let values: Vec<String> = fields.values().map(|value| value.to_string()).collect();
+                values.join(",")
+            },
+        };
+        writeln!(self.out, "{}", line).map_err(|err| Error::Io(err.to_string()))
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush().map_err(|err| Error::Io(err.to_string()))
+    }
+}
+
+/// run steps the simulator until the stop condition is met,
+/// on_iteration is called after every step
+pub fn run<F>(simulator: &mut Simulator, stop: &StopCondition, mut on_iteration: F) -> Result<RunSummary>
+where
+    F: FnMut(&Simulator) -> Result<()>,
+{
+    let strategy = simulator.get_config().ctx.get_strategy();
+    let mut historic_best = simulator.get_swarm().get_historic_best();
+    let mut converged_at = 0;
+    let mut reason = StopReason::MaxIterations;
+    let mut iterations = 0;
+
+    while iterations < stop.max_iterations {
+        simulator.step();
+        iterations += 1;
+        on_iteration(simulator)?;
+
+        let current = simulator.get_swarm().get_historic_best();
+        if strategy.is_better(&current, &historic_best) {
+            historic_best = current;
+            converged_at = iterations;
+        }
+
+        if let Some(target) = stop.target_score {
+            if reached(strategy, historic_best.score, target) {
+                reason = StopReason::TargetReached;
+                break;
+            }
+        }
+
+        if let Some(stagnation) = stop.stagnation {
+            if iterations - converged_at >= stagnation {
+                reason = StopReason::Stagnation;
+                break;
+            }
+        }
+    }
+
+    Ok(RunSummary {
+        iterations,
+        reason,
+        historic_best,
+        converged_at,
+    })
+}
+
+/// reached reports whether score is at least as good as target
+fn reached(strategy: Strategy, score: f64, target: f64) -> bool {
+    match strategy {
+        Strategy::Maximize => score >= target,
+        Strategy::Minimize => score <= target,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_replace_nested_fields() {
+        let mut config = serde_json::json!({"swarm": {"size": 5}, "ctx": {"goal": "Ackley"}});
+
+        apply_override(&mut config, "swarm.size=20").unwrap();
+        apply_override(&mut config, "ctx.goal=Griewank").unwrap();
+
+        assert_eq!(config["swarm"]["size"], 20);
+        assert_eq!(config["ctx"]["goal"], "Griewank");
+        assert!(apply_override(&mut config, "swarm.unknown=1").is_err());
+        assert!(apply_override(&mut config, "swarm.size").is_err());
+    }
+
+    #[test]
+    fn overrides_reach_defaulted_fields() {
+        // default.toml leaves the terrain seed out
+        let config = load_config(Path::new("configs/default.toml"), &["terrain.seed=5".to_string()]).unwrap();
+        assert_eq!(config.terrain.seed, Some(5));
+        let empty = load_config(Path::new("configs/default.toml"), &["swarm.size=0".to_string()]);
+        assert_eq!(empty.unwrap_err(), Error::invalid_config("swarm.size", "must be > 0"));
+
+        // configs written before subswarms existed run a single swarm
+        let mut value = read_config_value(Path::new("configs/default.toml")).unwrap();
+        value.as_object_mut().unwrap().remove("darwin");
+        let config: SimConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(config.darwin.max_swarms, 1);
+    }
+}
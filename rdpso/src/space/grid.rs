@@ -0,0 +1,429 @@
+//! grid holds regularly sampled scalar fields, ie measured concentrations or elevation models,
+//! and the parsers for the file formats they are usually stored in
+
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::wasm_bindgen;
+use crate::error::{Error, Result};
+use super::{Boundary, Pair};
+
+/// GridInterpolation selects how values between samples are computed
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+pub enum GridInterpolation {
+    #[default]
+    Bilinear,
+
+    /// Bicubic uses Catmull-Rom splines over the 4x4 surrounding samples
+    Bicubic,
+}
+
+/// FillPolicy decides the value of queries outside of the grid
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+pub enum FillPolicy {
+    /// Clamp extends the border samples outwards
+    #[default]
+    Clamp,
+
+    /// Constant returns the given value, it also replaces NODATA samples
+    Constant(f64),
+}
+
+/// GridFormat enumerates the supported grid files
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum GridFormat {
+    /// Csv stores one row per line, as comma separated values
+    Csv,
+
+    /// EsriAscii is the ESRI ASCII raster format, it carries its own georeferencing
+    EsriAscii,
+
+    /// RawF32 stores little endian f32 values row by row, the spec's width sets the row length
+    RawF32,
+
+    /// Pgm is a binary or plain grayscale PGM image, of 8 or 16 bits per sample
+    Pgm,
+
+    /// Png is a grayscale PNG image, of 8 or 16 bits per sample
+    Png,
+}
+
+impl GridFormat {
+    /// from_path guesses the format from the file extension: .csv, .asc, .f32/.raw, .pgm or .png
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "asc" => Some(Self::EsriAscii),
+            "f32" | "raw" => Some(Self::RawF32),
+            "pgm" => Some(Self::Pgm),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+}
+
+/// Grid is a scalar field sampled on a regular grid.
+/// Sample (row, col) lies at (origin_x + col * cell_size, origin_z + row * cell_size),
+/// thus rows run along the z axis and columns along the x axis.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+
+    /// values are stored row by row
+    values: Vec<f64>,
+
+    origin_x: f64,
+    origin_z: f64,
+    cell_size: f64,
+}
+
+impl Grid {
+
+    /// new builds a grid out of width * height values stored row by row,
+    /// missing samples may be given as NaN
+    pub fn new(width: usize, height: usize, values: Vec<f64>, origin_x: f64, origin_z: f64, cell_size: f64) -> Result<Self> {
+        if width < 2 || height < 2 {
+            return Err(Error::Parse(format!("grids need at least 2x2 samples, got {}x{}", width, height)));
+        }
+        if values.len() != width * height {
+            return Err(Error::Parse(format!("a {}x{} grid needs {} values, got {}", width, height, width * height, values.len())));
+        }
+        if !(cell_size > 0.0 && cell_size.is_finite()) {
+            return Err(Error::Parse(format!("cell size must be > 0, got {}", cell_size)));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            values,
+            origin_x,
+            origin_z,
+            cell_size,
+        })
+    }
+
+    /// from_rows builds a grid out of rows of equal length
+    pub fn from_rows(rows: &[Vec<f64>], origin_x: f64, origin_z: f64, cell_size: f64) -> Result<Self> {
+        let width = rows.first().map_or(0, |row| row.len());
+        if let Some((i, row)) = rows.iter().enumerate().find(|(_, row)| row.len() != width) {
+            return Err(Error::Parse(format!("row {} has {} values, expected {}", i, row.len(), width)));
+        }
+        Self::new(width, rows.len(), rows.concat(), origin_x, origin_z, cell_size)
+    }
+
+    /// read loads a grid file, its format is guessed from the extension unless given.
+    /// width is the row length of raw f32 files, ESRI ASCII grids ignore the given georeferencing.
+    pub fn read(path: &Path, format: Option<GridFormat>, width: usize, origin_x: f64, origin_z: f64, cell_size: f64) -> Result<Self> {
+        let format = format.or_else(|| GridFormat::from_path(path))
+            .ok_or_else(|| Error::Parse(format!("unknown grid format for {:?}, set it explicitly", path)))?;
+        let data = std::fs::read(path).map_err(|err| Error::Io(format!("{:?}: {}", path, err)))?;
+        Self::parse(&data, format, width, origin_x, origin_z, cell_size)
+            .map_err(|err| Error::Parse(format!("{:?}: {}", path, err)))
+    }
+
+    /// parse reads a grid out of a file's contents, see read
+    pub fn parse(data: &[u8], format: GridFormat, width: usize, origin_x: f64, origin_z: f64, cell_size: f64) -> Result<Self> {
+        let text = || std::str::from_utf8(data).map_err(|err| Error::Parse(err.to_string()));
+        match format {
+            GridFormat::Csv => Self::parse_csv(text()?, origin_x, origin_z, cell_size),
+            GridFormat::EsriAscii => Self::parse_esri_ascii(text()?),
+            GridFormat::RawF32 => Self::parse_raw_f32(data, width, origin_x, origin_z, cell_size),
+            GridFormat::Pgm => Self::parse_pgm(data, origin_x, origin_z, cell_size),
+            GridFormat::Png => Self::parse_png(data, origin_x, origin_z, cell_size),
+        }
+    }
+
+    /// parse_csv reads comma separated rows, blank lines and lines starting with # are skipped
+    pub fn parse_csv(text: &str, origin_x: f64, origin_z: f64, cell_size: f64) -> Result<Self> {
+        let mut rows = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let row = line.split(',')
+                .map(|value| value.trim().parse::<f64>()
+                    .map_err(|_| Error::Parse(format!("line {}: invalid value `{}`", i + 1, value.trim()))))
+                .collect::<Result<Vec<f64>>>()?;
+            rows.push(row);
+        }
+        Self::from_rows(&rows, origin_x, origin_z, cell_size)
+    }
+
+    /// parse_esri_ascii reads an ESRI ASCII grid, its header provides the georeferencing.
+    /// ESRI grids list the northernmost row first, it becomes the last row of the grid.
+    /// NODATA samples are read as NaN.
+    pub fn parse_esri_ascii(text: &str) -> Result<Self> {
+        let mut tokens = text.split_whitespace().peekable();
+        let (mut ncols, mut nrows, mut cell_size, mut nodata) = (None, None, None, None);
+        let (mut x, mut z, mut centered) = (None, None, false);
+
+        while let Some(key) = tokens.peek().filter(|token| token.starts_with(|c: char| c.is_ascii_alphabetic())) {
+            let key = key.to_ascii_lowercase();
+            tokens.next();
+            let value = tokens.next()
+                .and_then(|value| value.parse::<f64>().ok())
+                .ok_or_else(|| Error::Parse(format!("invalid value for ESRI header `{}`", key)))?;
+
+            match key.as_str() {
+                "ncols" => ncols = Some(value as usize),
+                "nrows" => nrows = Some(value as usize),
+                "xllcorner" => x = Some(value),
+                "yllcorner" => z = Some(value),
+                "xllcenter" => { x = Some(value); centered = true },
+                "yllcenter" => { z = Some(value); centered = true },
+                "cellsize" => cell_size = Some(value),
+                "nodata_value" => nodata = Some(value),
+                _ => return Err(Error::Parse(format!("unknown ESRI header `{}`", key))),
+            }
+        }
+
+        let missing = |key: &str| Error::Parse(format!("missing ESRI header `{}`", key));
+        let ncols = ncols.ok_or_else(|| missing("ncols"))?;
+        let nrows = nrows.ok_or_else(|| missing("nrows"))?;
+        let cell_size = cell_size.ok_or_else(|| missing("cellsize"))?;
+        let (mut x, mut z) = (x.ok_or_else(|| missing("xllcorner"))?, z.ok_or_else(|| missing("yllcorner"))?);
+        if !centered {
+            x += cell_size / 2.0;
+            z += cell_size / 2.0;
+        }
+
+        let mut values = tokens
+            .map(|token| token.parse::<f64>()
+                .map(|value| if Some(value) == nodata { f64::NAN } else { value })
+                .map_err(|_| Error::Parse(format!("invalid ESRI value `{}`", token))))
+            .collect::<Result<Vec<f64>>>()?;
+        if values.len() != ncols * nrows {
+            return Err(Error::Parse(format!("ESRI grid declares {}x{} values, got {}", ncols, nrows, values.len())));
+        }
+
+        // flip the rows so that z grows with the row index
+        let rows: Vec<Vec<f64>> = values.chunks(ncols.max(1)).rev().map(|row| row.to_vec()).collect();
+        values = rows.concat();
+        Self::new(ncols, nrows, values, x, z, cell_size)
+    }
+
+    /// parse_raw_f32 reads little endian f32 samples stored row by row, rows have width samples
+    pub fn parse_raw_f32(data: &[u8], width: usize, origin_x: f64, origin_z: f64, cell_size: f64) -> Result<Self> {
+        if !data.len().is_multiple_of(4) {
+            return Err(Error::Parse(format!("raw f32 data must have a multiple of 4 bytes, got {}", data.len())));
+        }
+        let values: Vec<f64> = data.chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64)
+            .collect();
+        if width == 0 || !values.len().is_multiple_of(width) {
+            return Err(Error::Parse(format!("{} raw samples can't be split in rows of {}", values.len(), width)));
+        }
+        let height = values.len() / width;
+        Self::new(width, height, values, origin_x, origin_z, cell_size)
+    }
+
+    /// parse_pgm reads a binary (P5) or plain (P2) PGM image of 8 or 16 bits per sample.
+    /// Images list the top row first, like ESRI grids it becomes the last row of the grid.
+    pub fn parse_pgm(data: &[u8], origin_x: f64, origin_z: f64, cell_size: f64) -> Result<Self> {
+        // the header is made of 4 whitespace separated tokens, comments run from # to the end of the line
+        let mut header = Vec::with_capacity(4);
+        let mut i = 0;
+        while header.len() < 4 {
+            match data.get(i) {
+                None => return Err(Error::Parse("truncated PGM header".to_string())),
+                Some(b'#') => while i < data.len() && data[i] != b'\n' { i += 1 },
+                Some(c) if c.is_ascii_whitespace() => i += 1,
+                Some(_) => {
+                    let start = i;
+                    while i < data.len() && !data[i].is_ascii_whitespace() { i += 1 }
+                    header.push(String::from_utf8_lossy(&data[start..i]).to_string());
+                },
+            }
+        }
+
+        let number = |token: &str| token.parse::<usize>()
+            .map_err(|_| Error::Parse(format!("invalid PGM header value `{}`", token)));
+        let (width, height, max) = (number(&header[1])?, number(&header[2])?, number(&header[3])?);
+        if max == 0 || max > 65535 {
+            return Err(Error::Parse(format!("PGM max value must be in [1, 65535], got {}", max)));
+        }
+
+        let values: Vec<f64> = match header[0].as_str() {
+            "P5" => {
+                // a single whitespace separates the header from the samples
+                let samples = &data[(i + 1).min(data.len())..];
+                let depth = if max < 256 { 1 } else { 2 };
+                if samples.len() < width * height * depth {
+                    return Err(Error::Parse(format!("a {}x{} PGM needs {} bytes of samples, got {}", width, height, width * height * depth, samples.len())));
+                }
+                samples.chunks_exact(depth)
+                    .take(width * height)
+                    .map(|bytes| if depth == 1 { bytes[0] as f64 } else { u16::from_be_bytes([bytes[0], bytes[1]]) as f64 })
+                    .collect()
+            },
+            "P2" => String::from_utf8_lossy(&data[i..]).split_whitespace()
+                .map(|token| token.parse::<f64>().map_err(|_| Error::Parse(format!("invalid PGM value `{}`", token))))
+                .collect::<Result<Vec<f64>>>()?,
+            magic => return Err(Error::Parse(format!("unsupported PGM magic number `{}`, expected P5 or P2", magic))),
+        };
+
+        Self::from_image_rows(width, height, values, origin_x, origin_z, cell_size)
+    }
+
+    /// parse_png reads a grayscale PNG image of 8 or 16 bits per sample,
+    /// rows are flipped as in parse_pgm
+    pub fn parse_png(data: &[u8], origin_x: f64, origin_z: f64, cell_size: f64) -> Result<Self> {
+        let invalid = |err: png::DecodingError| Error::Parse(format!("invalid PNG: {}", err));
+        let mut reader = png::Decoder::new(data).read_info().map_err(invalid)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buffer).map_err(invalid)?;
+        if frame.color_type != png::ColorType::Grayscale {
+            return Err(Error::Parse(format!("PNG grids must be grayscale, got {:?}", frame.color_type)));
+        }
+
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        let rows = buffer[..frame.buffer_size()].chunks_exact(frame.line_size);
+        let values: Vec<f64> = match frame.bit_depth {
+            png::BitDepth::Eight => rows.flat_map(|row| row[..width].iter().map(|&v| v as f64)).collect(),
+            png::BitDepth::Sixteen => rows
+                .flat_map(|row| row[..2 * width].chunks_exact(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as f64))
+                .collect(),
+            depth => return Err(Error::Parse(format!("PNG grids must have 8 or 16 bits per sample, got {:?}", depth))),
+        };
+
+        Self::from_image_rows(width, height, values, origin_x, origin_z, cell_size)
+    }
+
+    /// from_image_rows builds a grid out of image samples, which list the top row first
+    fn from_image_rows(width: usize, height: usize, values: Vec<f64>, origin_x: f64, origin_z: f64, cell_size: f64) -> Result<Self> {
+        if values.len() != width * height {
+            return Err(Error::Parse(format!("a {}x{} image needs {} values, got {}", width, height, width * height, values.len())));
+        }
+        let values = values.chunks(width.max(1)).rev().flatten().copied().collect();
+        Self::new(width, height, values, origin_x, origin_z, cell_size)
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// get_origin returns the position of the first sample
+    pub fn get_origin(&self) -> Pair {
+        [self.origin_x, self.origin_z]
+    }
+
+    pub fn get_cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    pub fn get_values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// get_boundary returns the area spanned by the samples
+    pub fn get_boundary(&self) -> Boundary {
+        Boundary::new(
+            self.origin_x,
+            self.origin_x + (self.width - 1) as f64 * self.cell_size,
+            self.origin_z,
+            self.origin_z + (self.height - 1) as f64 * self.cell_size,
+        )
+    }
+
+    /// get returns the sample at (row, col), indices are clamped to the grid
+    fn get(&self, row: isize, col: isize) -> f64 {
+        let row = row.clamp(0, self.height as isize - 1) as usize;
+        let col = col.clamp(0, self.width as isize - 1) as usize;
+        self.values[row * self.width + col]
+    }
+
+    /// sample interpolates the field at (x, z), queries outside of the grid follow the fill policy
+    pub fn sample(&self, x: f64, z: f64, interpolation: GridInterpolation, fill: FillPolicy) -> f64 {
+        let max_u = (self.width - 1) as f64;
+        let max_v = (self.height - 1) as f64;
+        let mut u = (x - self.origin_x) / self.cell_size;
+        let mut v = (z - self.origin_z) / self.cell_size;
+
+        let inside = (0.0..=max_u).contains(&u) && (0.0..=max_v).contains(&v);
+        match fill {
+            FillPolicy::Constant(value) if !inside => return value,
+            _ => {
+                u = u.clamp(0.0, max_u);
+                v = v.clamp(0.0, max_v);
+            },
+        }
+
+        // the last row and column are reached with a fraction of 1 from the previous cell
+        let col = (u.floor() as isize).min(self.width as isize - 2);
+        let row = (v.floor() as isize).min(self.height as isize - 2);
+        let (fu, fv) = (u - col as f64, v - row as f64);
+
+        let value = match interpolation {
+            GridInterpolation::Bilinear => {
+                let top = lerp(self.get(row, col), self.get(row, col + 1), fu);
+                let bottom = lerp(self.get(row + 1, col), self.get(row + 1, col + 1), fu);
+                lerp(top, bottom, fv)
+            },
+            GridInterpolation::Bicubic => {
+                let mut rows = [0.0; 4];
+                for (i, r) in (row - 1..=row + 2).enumerate() {
+                    rows[i] = catmull_rom(self.get(r, col - 1), self.get(r, col), self.get(r, col + 1), self.get(r, col + 2), fu);
+                }
+                catmull_rom(rows[0], rows[1], rows[2], rows[3], fv)
+            },
+        };
+
+        match fill {
+            FillPolicy::Constant(fallback) if value.is_nan() => fallback,
+            _ => value,
+        }
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// catmull_rom interpolates between p1 and p2, p0 and p3 set the tangents
+fn catmull_rom(p0: f64, p1: f64, p2: f64, p3: f64, t: f64) -> f64 {
+    p1 + 0.5 * t * (p2 - p0 + t * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3 + t * (3.0 * (p1 - p2) + p3 - p0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_agree_on_georeferenced_samples() {
+        let csv = Grid::parse_csv("0, 1, 2\n3, 4, 5\n", 10.0, 20.0, 2.0).unwrap();
+        let esri = Grid::parse_esri_ascii("ncols 3\nnrows 2\nxllcorner 9\nyllcorner 19\ncellsize 2\nNODATA_value -9999\n3 4 5\n0 1 2\n").unwrap();
+        let raw: Vec<u8> = [0.0f32, 1.0, 2.0, 3.0, 4.0, 5.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let raw = Grid::parse_raw_f32(&raw, 3, 10.0, 20.0, 2.0).unwrap();
+        assert_eq!(csv, esri);
+        assert_eq!(csv, raw);
+
+        assert_eq!(csv.sample(12.0, 22.0, GridInterpolation::Bilinear, FillPolicy::Clamp), 4.0);
+        assert_eq!(csv.sample(11.0, 21.0, GridInterpolation::Bilinear, FillPolicy::Clamp), 2.0);
+
+        // away from the borders both interpolations reproduce linear fields
+        let linear = Grid::new(4, 4, (0..16).map(|i| i as f64).collect(), 0.0, 0.0, 1.0).unwrap();
+        assert_eq!(linear.sample(1.5, 1.5, GridInterpolation::Bicubic, FillPolicy::Clamp), 7.5);
+        assert_eq!(linear.sample(1.5, 1.5, GridInterpolation::Bilinear, FillPolicy::Clamp), 7.5);
+
+        assert_eq!(csv.sample(0.0, 0.0, GridInterpolation::Bilinear, FillPolicy::Clamp), 0.0);
+        assert_eq!(csv.sample(0.0, 0.0, GridInterpolation::Bilinear, FillPolicy::Constant(-1.0)), -1.0);
+
+        // images list the top row first, as ESRI grids do
+        let mut pgm = b"P5\n# 16 bits\n3 2\n65535\n".to_vec();
+        pgm.extend([3u16, 4, 5, 0, 1, 2].iter().flat_map(|v| v.to_be_bytes()));
+        assert_eq!(Grid::parse_pgm(&pgm, 10.0, 20.0, 2.0).unwrap(), csv);
+        assert_eq!(Grid::parse_pgm(b"P2 3 2 255 3 4 5 0 1 2", 10.0, 20.0, 2.0).unwrap(), csv);
+    }
+}
@@ -1,8 +1,13 @@
 use crate::wasm_bindgen;
+use crate::error::{Error, Result};
+use serde::{Serialize, Deserialize};
 
 mod vector;
 pub use vector::Vector;
 
+mod grid;
+pub use grid::{Grid, GridFormat, GridInterpolation, FillPolicy};
+
 
 pub type Pair = [f64; 2];
 
@@ -26,6 +31,7 @@ impl Point2D {
 
 #[derive(Debug, PartialEq,Copy, Clone)]
 #[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
 pub struct Boundary {
     min_x: f64,
     max_x: f64,
@@ -46,10 +52,10 @@ impl Boundary {
         }
 
         Self{
-            min_x: min_x,
-            max_x: max_x,
-            min_z: min_z,
-            max_z: max_z,
+            min_x,
+            max_x,
+            min_z,
+            max_z,
         }
     }
 
@@ -57,7 +63,7 @@ impl Boundary {
     /// that is, if one of vec's components is outside the threshold
     /// that coordinate is clipped to the boundaries defined by the region vectors
     pub fn clip(&self, vec: &Vector) -> Vector {
-        let mut vec = vec.clone();
+        let mut vec = *vec;
 
         if vec.x < self.min_x {
             vec.x = self.min_x;
@@ -73,17 +79,37 @@ impl Boundary {
 
         vec
     }
+
+    pub fn get_min_x(&self) -> f64 {
+        self.min_x
+    }
+
+    pub fn get_max_x(&self) -> f64 {
+        self.max_x
+    }
+
+    pub fn get_min_z(&self) -> f64 {
+        self.min_z
+    }
+
+    pub fn get_max_z(&self) -> f64 {
+        self.max_z
+    }
+
+    /// contains reports whether vec's x and z components are within the boundary
+    pub fn contains(&self, vec: &Vector) -> bool {
+        vec.x >= self.min_x && vec.x <= self.max_x && vec.z >= self.min_z && vec.z <= self.max_z
+    }
 }
 
 
 /// Mapper maps one interval onto another
 #[wasm_bindgen]
 #[derive(Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize)]
 pub struct Mapper {
     start1: f64,
-    stop1: f64,
     start2: f64,
-    stop2: f64,
     delta1: f64,
     delta2: f64,
 }
@@ -93,10 +119,8 @@ impl Mapper {
 
     pub fn new(start1: f64, stop1: f64, start2: f64, stop2: f64) -> Self {
         Self {
-            start1: start1,
-            stop1: stop1,
-            start2: start2,
-            stop2: stop2,
+            start1,
+            start2,
             delta1: stop1 - start1,
             delta2: stop2 - start2,
         }
@@ -105,15 +129,18 @@ impl Mapper {
     pub fn map(&self, x:f64) -> f64 {
         ( self.delta2 * (x - self.start1) / self.delta1 ) + self.start2
     }
+
+    /// get_ratio returns how much map stretches distances, its derivative
+    pub fn get_ratio(&self) -> f64 {
+        self.delta2 / self.delta1
+    }
 }
 
 impl Mapper {
     pub fn new_from_pair(from: Pair, to: Pair) -> Self {
         Self {
             start1: from[0],
-            stop1: from[1],
             start2: to[0],
-            stop2: to[1],
             delta1: from[1] - from[0],
             delta2: to[1] - to[0],
         }
@@ -123,6 +150,7 @@ impl Mapper {
 /// Domain represents an interval bound domain, from a to b
 #[wasm_bindgen]
 #[derive(Copy, Debug, Clone)]
+#[derive(Serialize, Deserialize)]
 pub struct Domain {
     a: f64,
     b: f64,
@@ -131,32 +159,66 @@ pub struct Domain {
 impl Domain {
 
     pub fn new(a: f64, b: f64) -> Self {
-        Self { a: a, b: b }
+        Self { a, b }
     }
 
     pub fn contains(&self, x: f64) -> bool {
-        if x >= self.a && x <= self.b {
-            true
-        } else {
-            false
-        }
+        x >= self.a && x <= self.b
     }
 }
 
 
+/// InterpolationMode selects the curve drawn between the breakpoints of a PiecewieseInterpolator
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+pub enum InterpolationMode {
+    /// Linear joins the breakpoints with straight segments
+    #[default]
+    Linear = 0,
+
+    /// MonotoneCubic is a Fritsch-Butland cubic Hermite spline, smooth and without overshoots:
+    /// it's monotone wherever the breakpoints are
+    MonotoneCubic = 1,
+
+    /// CatmullRom is a cubic Hermite spline with finite difference tangents, smooth but it may overshoot
+    CatmullRom = 2,
+}
+
 #[derive(Debug, Clone)]
 #[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
 pub struct PiecewieseInterpolator {
     domains: Vec<Domain>,
     mappers: Vec<Mapper>,
+
+    #[serde(default)]
+    mode: InterpolationMode,
+
+    /// tangents holds the slope at every breakpoint, empty for linear interpolators
+    #[serde(default)]
+    tangents: Vec<f64>,
 }
 
 #[wasm_bindgen]
 impl PiecewieseInterpolator {
     
-    pub fn new(from: Vec<f64>, to: Vec<f64>) -> Option<PiecewieseInterpolator> {
+    pub fn new(from: Vec<f64>, to: Vec<f64>) -> Result<PiecewieseInterpolator> {
+        Self::new_with_mode(from, to, InterpolationMode::Linear)
+    }
+
+    /// new_with_mode builds an interpolator through the (from, to) breakpoints with the given curve
+    pub fn new_with_mode(from: Vec<f64>, to: Vec<f64>, mode: InterpolationMode) -> Result<PiecewieseInterpolator> {
         if from.len() != to.len() {
-            return None;
+            return Err(Error::InvalidInterpolation(format!("{} breakpoints but {} values", from.len(), to.len())));
+        }
+
+        if from.len() < 2 {
+            return Err(Error::InvalidInterpolation("at least 2 breakpoints are required".to_string()));
+        }
+
+        if from.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(Error::InvalidInterpolation("breakpoints must be strictly increasing".to_string()));
         }
 
         let mut domains = Vec::with_capacity(from.len() - 1);
@@ -169,10 +231,18 @@ impl PiecewieseInterpolator {
             mappers.push(mapper);
         }
 
-        Some(
+        let tangents = match mode {
+            InterpolationMode::Linear => Vec::new(),
+            InterpolationMode::MonotoneCubic => monotone_tangents(&from, &to),
+            InterpolationMode::CatmullRom => catmull_rom_tangents(&from, &to),
+        };
+
+        Ok(
             Self{
-                mappers: mappers,
-                domains: domains,
+                mappers,
+                domains,
+                mode,
+                tangents,
             }
         )
     }
@@ -180,12 +250,131 @@ impl PiecewieseInterpolator {
     /// map interpolates value v according to the piecewise interpolation
     /// if v is not part of the domain, returns None
     pub fn map(&self, v: f64) -> Option<f64> {
-        for (domain, mapper) in Iterator::zip(self.domains.iter(), self.mappers.iter()) {
+        for (i, domain) in self.domains.iter().enumerate() {
             if domain.contains(v) {
-                return Some(mapper.map(v))
+                return Some(self.map_segment(i, v))
             }
         }
         None
     }
 
-}
\ No newline at end of file
+    /// map_clamped clamps v to the interpolator's domain before mapping it,
+    /// thus it never fails
+    pub fn map_clamped(&self, v: f64) -> f64 {
+        let (first, last) = (self.domains[0], self.domains[self.domains.len() - 1]);
+        let v = v.clamp(first.a, last.b);
+        self.map(v).unwrap_or_else(|| self.map_segment(self.mappers.len() - 1, v))
+    }
+
+    /// derivative_clamped returns the slope of map_clamped at v, 0 outside the domain
+    pub fn derivative_clamped(&self, v: f64) -> f64 {
+        match self.domains.iter().position(|domain| domain.contains(v)) {
+            Some(i) => self.derivative_segment(i, v),
+            None => 0.0,
+        }
+    }
+
+    pub fn get_mode(&self) -> InterpolationMode {
+        self.mode
+    }
+}
+
+impl PiecewieseInterpolator {
+
+    /// map_segment interpolates v within the ith segment
+    fn map_segment(&self, i: usize, v: f64) -> f64 {
+        let mapper = &self.mappers[i];
+        if self.tangents.is_empty() {
+            return mapper.map(v);
+        }
+
+        // cubic Hermite basis over the segment, the tangents are scaled by its width
+        let (width, y0) = (mapper.delta1, mapper.start2);
+        let y1 = y0 + mapper.delta2;
+        let t = (v - mapper.start1) / width;
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * width * self.tangents[i]
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * width * self.tangents[i + 1]
+    }
+
+    /// derivative_segment differentiates map_segment within the ith segment
+    fn derivative_segment(&self, i: usize, v: f64) -> f64 {
+        let mapper = &self.mappers[i];
+        if self.tangents.is_empty() {
+            return mapper.get_ratio();
+        }
+
+        let (width, y0) = (mapper.delta1, mapper.start2);
+        let y1 = y0 + mapper.delta2;
+        let t = (v - mapper.start1) / width;
+        let t2 = t * t;
+        ((6.0 * t2 - 6.0 * t) * (y0 - y1)) / width
+            + (3.0 * t2 - 4.0 * t + 1.0) * self.tangents[i]
+            + (3.0 * t2 - 2.0 * t) * self.tangents[i + 1]
+    }
+}
+
+/// catmull_rom_tangents takes the slope between the neighbours of every breakpoint,
+/// the end breakpoints use their only segment's slope
+fn catmull_rom_tangents(x: &[f64], y: &[f64]) -> Vec<f64> {
+    let last = x.len() - 1;
+    (0..=last)
+        .map(|i| {
+            let (a, b) = (i.saturating_sub(1), (i + 1).min(last));
+            (y[b] - y[a]) / (x[b] - x[a])
+        })
+        .collect()
+}
+
+/// monotone_tangents computes the Fritsch-Butland tangents: the weighted harmonic mean of the slopes
+/// around a breakpoint, or 0 at local extrema, which keeps monotone data monotone
+fn monotone_tangents(x: &[f64], y: &[f64]) -> Vec<f64> {
+    let slopes: Vec<f64> = (0..x.len() - 1).map(|i| (y[i + 1] - y[i]) / (x[i + 1] - x[i])).collect();
+    let mut tangents = Vec::with_capacity(x.len());
+    tangents.push(slopes[0]);
+    for i in 1..slopes.len() {
+        let (d0, d1) = (slopes[i - 1], slopes[i]);
+        let (h0, h1) = (x[i] - x[i - 1], x[i + 1] - x[i]);
+        tangents.push(if d0 * d1 <= 0.0 {
+            0.0
+        } else {
+            3.0 * (h0 + h1) / ((2.0 * h1 + h0) / d0 + (h1 + 2.0 * h0) / d1)
+        });
+    }
+    tangents.push(slopes[slopes.len() - 1]);
+    tangents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolators_go_through_their_breakpoints() {
+        let (from, to) = (vec![0.0, 0.25, 0.5, 1.0], vec![0.0, 0.0, 30.0, 500.0]);
+        let linear = PiecewieseInterpolator::new(from.clone(), to.clone()).unwrap();
+        let monotone = PiecewieseInterpolator::new_with_mode(from.clone(), to.clone(), InterpolationMode::MonotoneCubic).unwrap();
+        let catmull_rom = PiecewieseInterpolator::new_with_mode(from.clone(), to.clone(), InterpolationMode::CatmullRom).unwrap();
+        for (x, y) in from.iter().zip(to.iter()) {
+            for interpolator in [&linear, &monotone, &catmull_rom].iter() {
+                assert!((interpolator.map_clamped(*x) - y).abs() < 1e-9);
+            }
+        }
+        assert_eq!(linear.map(0.75), Some(265.0));
+
+        // the flat start stays flat and never dips below 0 with monotone tangents, Catmull-Rom overshoots
+        let samples: Vec<f64> = (0..=100).map(|i| i as f64 / 100.0).collect();
+        assert!(samples.windows(2).all(|pair| monotone.map_clamped(pair[1]) >= monotone.map_clamped(pair[0])));
+        assert_eq!(monotone.map_clamped(0.1), 0.0);
+        assert!(samples.iter().any(|&x| catmull_rom.map_clamped(x) < 0.0));
+        assert_eq!(monotone.map_clamped(2.0), 500.0);
+
+        // the slopes follow the curves, and vanish where they're clamped
+        assert_eq!(linear.derivative_clamped(0.75), 940.0);
+        assert_eq!(catmull_rom.derivative_clamped(-1.0), 0.0);
+        let expected = (catmull_rom.map_clamped(0.4 + 1e-6) - catmull_rom.map_clamped(0.4 - 1e-6)) / 2e-6;
+        assert!((catmull_rom.derivative_clamped(0.4) - expected).abs() < 1e-4);
+    }
+}
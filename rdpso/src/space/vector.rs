@@ -97,7 +97,7 @@ impl Vector
     /// rotate rotates a vector by the given angle (in radians)
     pub fn rotate_xy(&self, angle: f64) -> Vector
      {
-        let (sin, cos) = (angle.sin(), angle.cos());
+        let (sin, cos) = (libm::sin(angle), libm::cos(angle));
         Vector
          {
             x: self.x * cos + self.y * sin,
@@ -109,7 +109,7 @@ impl Vector
     /// rotate rotates a vector by the given angle (in radians)
     pub fn rotate_xz(&self, angle: f64) -> Vector
      {
-        let (sin, cos) = (angle.sin(), angle.cos());
+        let (sin, cos) = (libm::sin(angle), libm::cos(angle));
         Vector
          {
             x: self.x * cos + self.z * sin,
@@ -121,9 +121,9 @@ impl Vector
     pub fn component_mult(&self, other: &Vector) -> Vector
      {
         Self {
-            x: self.x * self.x,
-            y: self.y * self.y,
-            z: self.z * self.z,
+            x: self.x * other.x,
+            y: self.y * other.y,
+            z: self.z * other.z,
         }
     }
 
@@ -194,6 +194,7 @@ mod tests {
         assert_eq!(c, Vector::new(4.0, 6.0, 9.0))
     }
 
+    #[test]
     fn vec_sub() {
         let a = Vector::new(1.0, 2.0, 3.0);
         let b = Vector::new(3.0, 4.0, 1.0);
@@ -203,6 +204,7 @@ mod tests {
         assert_eq!(c, Vector::new(-2.0, -2.0, 2.0))
     }
 
+    #[test]
     fn scalar_mult() {
         let a = Vector::new(1.0, 2.0, 3.0);
 
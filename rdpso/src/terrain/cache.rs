@@ -0,0 +1,94 @@
+use serde::{Serialize, Deserialize};
+
+use crate::wasm_bindgen;
+use crate::error::Result;
+use crate::space::{Boundary, Grid, GridInterpolation, FillPolicy};
+
+/// CacheError summarizes how far the baked heights are from the analytic ones
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct CacheError {
+    pub max_error: f64,
+
+    /// rms_error is the root mean square of the errors
+    pub rms_error: f64,
+
+    pub samples: usize,
+}
+
+/// bake samples height on a resolution x resolution grid spanning the boundary
+pub fn bake(height: impl Fn(f64, f64) -> f64, boundary: Boundary, resolution: usize) -> Result<Grid> {
+    let width = boundary.get_max_x() - boundary.get_min_x();
+    let depth = boundary.get_max_z() - boundary.get_min_z();
+    let cell_size = width.max(depth) / (resolution - 1) as f64;
+
+    // the grid covers the boundary, its last row and column may slightly overrun it
+    let columns = (width / cell_size).ceil() as usize + 1;
+    let rows = (depth / cell_size).ceil() as usize + 1;
+    let (x0, z0) = (boundary.get_min_x(), boundary.get_min_z());
+    let values = (0..rows)
+        .flat_map(|row| (0..columns).map(move |col| (row, col)))
+        .map(|(row, col)| height(x0 + col as f64 * cell_size, z0 + row as f64 * cell_size))
+        .collect();
+    Grid::new(columns, rows, values, x0, z0, cell_size)
+}
+
+/// compare measures the error of the cache against analytic on samples x samples points.
+/// The points are the centers of cache cells spread over the grid, where bilinear interpolation is the furthest from its samples.
+pub fn compare(cache: &Grid, analytic: impl Fn(f64, f64) -> f64, samples: usize) -> CacheError {
+    let samples = samples.max(1);
+    let [x0, z0] = cache.get_origin();
+    let cell_size = cache.get_cell_size();
+    let center = |k: usize, cells: usize| ((k as f64 + 0.5) * cells as f64 / samples as f64).floor() + 0.5;
+    let (columns, rows) = (cache.get_width() - 1, cache.get_height() - 1);
+
+    let (mut max_error, mut squares) = (0.0f64, 0.0);
+    for i in 0..samples {
+        for j in 0..samples {
+            let x = x0 + center(i, columns) * cell_size;
+            let z = z0 + center(j, rows) * cell_size;
+            let error = (cache.sample(x, z, GridInterpolation::Bilinear, FillPolicy::Clamp) - analytic(x, z)).abs();
+            max_error = max_error.max(error);
+            squares += error * error;
+        }
+    }
+
+    let count = samples * samples;
+    CacheError {
+        max_error,
+        rms_error: (squares / count as f64).sqrt(),
+        samples: count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::terrain::{Config, Terrain};
+    use crate::utils::Rng;
+
+    #[test]
+    fn baked_terrains_stay_close_to_the_noise() {
+        let baked = |resolution: usize| {
+            let mut config = Config::new_from_size(1000);
+            config.cache_resolution = resolution;
+            Terrain::new(config, &mut Rng::new(3)).unwrap()
+        };
+        let (coarse, fine) = (baked(101), baked(201));
+        assert!(fine.is_cached());
+
+        // samples are exact, the heights in between are interpolated
+        assert_eq!(fine.get_height(-500.0, 495.0), fine.get_analytic_height(-500.0, 495.0));
+        let (coarse_error, fine_error) = (coarse.compare_cache(50), fine.compare_cache(50));
+        assert_eq!(fine_error.samples, 2500);
+        assert!(fine_error.rms_error < coarse_error.rms_error, "{:?} {:?}", fine_error, coarse_error);
+        assert!(fine_error.max_error > 0.0 && fine_error.max_error < 10.0, "{:?}", fine_error);
+
+        // the cache is baked again on deserialization rather than stored
+        let bytes = rmp_serde::to_vec(&fine).unwrap();
+        assert!(bytes.len() < 201 * 201, "{} bytes", bytes.len());
+        let restored: Terrain = rmp_serde::from_slice(&bytes).unwrap();
+        assert!(restored.is_cached());
+        assert_eq!(restored.get_height(-123.0, 456.0), fine.get_height(-123.0, 456.0));
+    }
+}
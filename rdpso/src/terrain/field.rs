@@ -0,0 +1,49 @@
+use std::fmt;
+
+use crate::space::{Boundary, Vector};
+use super::Terrain;
+
+/// NORMAL_STEP is the distance used by the finite differences of HeightField::get_normal
+const NORMAL_STEP: f64 = 1e-3;
+
+/// HeightField is a surface y = h(x, z) over a rectangular area,
+/// it's all the physics and the particles need to know about the ground
+pub trait HeightField: fmt::Debug {
+    fn get_height(&self, x: f64, z: f64) -> f64;
+
+    /// get_boundary returns the area the surface is defined on
+    fn get_boundary(&self) -> Boundary;
+
+    /// get_normal returns the upward unit normal at (x, z), by central differences unless overridden
+    fn get_normal(&self, x: f64, z: f64) -> Vector {
+        let dx = (self.get_height(x + NORMAL_STEP, z) - self.get_height(x - NORMAL_STEP, z)) / (2.0 * NORMAL_STEP);
+        let dz = (self.get_height(x, z + NORMAL_STEP) - self.get_height(x, z - NORMAL_STEP)) / (2.0 * NORMAL_STEP);
+        Vector::new(-dx, 1.0, -dz).unit()
+    }
+
+    /// get_point_from_parametric maps (u, v) in [0, 1]² onto the boundary and returns the surface point there
+    fn get_point_from_parametric(&self, u: f64, v: f64) -> Vector {
+        let boundary = self.get_boundary();
+        let x = boundary.get_min_x() + u * (boundary.get_max_x() - boundary.get_min_x());
+        let z = boundary.get_min_z() + v * (boundary.get_max_z() - boundary.get_min_z());
+        Vector::new(x, self.get_height(x, z), z)
+    }
+}
+
+impl HeightField for Terrain {
+    fn get_height(&self, x: f64, z: f64) -> f64 {
+        Terrain::get_height(self, x, z)
+    }
+
+    fn get_boundary(&self) -> Boundary {
+        Terrain::get_boundary(self)
+    }
+
+    fn get_normal(&self, x: f64, z: f64) -> Vector {
+        Terrain::get_normal(self, x, z)
+    }
+
+    fn get_point_from_parametric(&self, u: f64, v: f64) -> Vector {
+        Terrain::get_point_from_parametric(self, u, v)
+    }
+}
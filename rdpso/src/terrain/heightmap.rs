@@ -0,0 +1,172 @@
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{Error, Result};
+use crate::space::{Grid, GridFormat, Pair};
+
+fn default_vertical_scale() -> f64 {
+    1.0
+}
+
+/// HeightmapSpec describes a terrain read from a digital elevation model.
+/// In configs it's written as `heightmap = { path = "site.png", vertical_scale = 0.05, cell_size = 2.0 }`.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct HeightmapSpec {
+    /// path is the elevation file, relative paths are resolved against the working directory
+    pub path: String,
+
+    /// format overrides the format guessed from the path's extension
+    #[serde(default)]
+    pub format: Option<GridFormat>,
+
+    /// width is the row length of raw f32 files
+    #[serde(default)]
+    pub width: usize,
+
+    /// vertical_scale converts samples into heights, ie meters per gray level
+    #[serde(default = "default_vertical_scale")]
+    pub vertical_scale: f64,
+
+    /// cell_size is the distance between samples, it defaults to the ESRI header's or 1
+    #[serde(default)]
+    pub cell_size: Option<f64>,
+
+    /// origin is the [x, z] world position of the first sample,
+    /// the heightmap is centered on the world origin if it's missing
+    #[serde(default)]
+    pub origin: Option<Pair>,
+}
+
+impl HeightmapSpec {
+
+    /// load reads the elevation grid, scaled and placed in world coordinates.
+    /// NODATA samples are filled with the lowest valid height.
+    pub fn load(&self) -> Result<Grid> {
+        let path = Path::new(&self.path);
+        let grid = Grid::read(path, self.format, self.width, 0.0, 0.0, self.cell_size.unwrap_or(1.0))?;
+        self.place(grid).map_err(|err| Error::Parse(format!("{:?}: {}", path, err)))
+    }
+
+    /// parse reads the elevation grid out of the file's contents
+    pub fn parse(&self, data: &[u8], format: GridFormat) -> Result<Grid> {
+        self.place(Grid::parse(data, format, self.width, 0.0, 0.0, self.cell_size.unwrap_or(1.0))?)
+    }
+
+    /// place scales the samples of the elevation file and moves them to their world position
+    fn place(&self, grid: Grid) -> Result<Grid> {
+        let lowest = grid.get_values().iter()
+            .copied()
+            .filter(|value| value.is_finite())
+            .reduce(f64::min)
+            .ok_or_else(|| Error::Parse("heightmaps need at least one valid sample".to_string()))?;
+        let heights = grid.get_values().iter()
+            .map(|&value| (if value.is_finite() { value } else { lowest }) * self.vertical_scale)
+            .collect();
+
+        let (width, height) = (grid.get_width(), grid.get_height());
+        let cell_size = self.cell_size.unwrap_or_else(|| grid.get_cell_size());
+        let [x, z] = self.origin.unwrap_or([
+            -((width - 1) as f64) * cell_size / 2.0,
+            -((height - 1) as f64) * cell_size / 2.0,
+        ]);
+        Grid::new(width, height, heights, x, z, cell_size)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.vertical_scale.is_finite() || self.vertical_scale <= 0.0 {
+            return Err(Error::invalid_config("terrain.heightmap.vertical_scale", "must be > 0"));
+        }
+        if self.cell_size.is_some_and(|size| !size.is_finite() || size <= 0.0) {
+            return Err(Error::invalid_config("terrain.heightmap.cell_size", "must be > 0"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::space::{GridInterpolation, FillPolicy};
+    use crate::tests::config;
+    use crate::{space, terrain, utils, Simulator};
+
+    #[test]
+    fn images_become_scaled_centered_grids() {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 3, 2);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Sixteen);
+        let samples: Vec<u8> = [300u16, 400, 500, 0, 100, 200].iter().flat_map(|v| v.to_be_bytes()).collect();
+        encoder.write_header().unwrap().write_image_data(&samples).unwrap();
+
+        let spec = HeightmapSpec {
+            path: "site.png".to_string(),
+            format: None,
+            width: 0,
+            vertical_scale: 0.5,
+            cell_size: Some(10.0),
+            origin: None,
+        };
+        assert_eq!(GridFormat::from_path(Path::new(&spec.path)), Some(GridFormat::Png));
+
+        // the bottom row of the image lies at the lowest z, the map is centered on the origin
+        let grid = spec.parse(&png, GridFormat::Png).unwrap();
+        assert_eq!(grid.get_origin(), [-10.0, -5.0]);
+        assert_eq!(grid.sample(-10.0, -5.0, GridInterpolation::Bilinear, FillPolicy::Clamp), 0.0);
+        assert_eq!(grid.sample(5.0, 0.0, GridInterpolation::Bilinear, FillPolicy::Clamp), 150.0);
+
+        let esri = "ncols 2\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 30\nNODATA_value -9999\n-9999 4\n2 6\n";
+        let grid = spec.parse(esri.as_bytes(), GridFormat::EsriAscii).unwrap();
+        assert_eq!(grid.get_values(), &[1.0, 3.0, 1.0, 2.0][..]);
+    }
+
+    #[test]
+    fn heightmap_terrains_replace_the_noise() {
+        let path = std::env::temp_dir().join("rdpso_heightmap.pgm");
+        let mut pgm = b"P5 4 3 255\n".to_vec();
+        pgm.extend_from_slice(&[0, 10, 20, 30, 10, 20, 30, 40, 20, 30, 40, 50]);
+        std::fs::write(&path, &pgm).unwrap();
+
+        let mut config = config(4);
+        config.terrain.heightmap = Some(HeightmapSpec {
+            path: path.to_string_lossy().to_string(),
+            format: None,
+            width: 0,
+            vertical_scale: 2.0,
+            cell_size: Some(100.0),
+            origin: None,
+        });
+        let mut simulator = Simulator::new(config.clone()).unwrap();
+        let terrain = &simulator.terrain;
+        assert_eq!(terrain.get_boundary(), space::Boundary::new(-150.0, 150.0, -100.0, 100.0));
+        assert_eq!(terrain.get_point_from_parametric(1.0, 0.0), space::Vector::new(150.0, 100.0, -100.0));
+        assert_eq!(terrain.get_height(-100.0, 50.0), 20.0);
+
+        // no Perlin noise is drawn for heightmaps
+        let mut rng = utils::Rng::new(0);
+        terrain::Terrain::new(config.terrain.clone(), &mut rng).unwrap();
+        assert_eq!(rng, utils::Rng::new(0));
+        for _ in 0..20 {
+            simulator.step();
+        }
+        for particle in simulator.get_swarm().get_particles() {
+            let position = particle.get_position();
+            assert!(simulator.terrain.get_boundary().contains(&position));
+            assert!(position.y >= simulator.terrain.get_height(position.x, position.z));
+        }
+
+        // the goal is drawn over the heightmap's area, wherever it lies
+        let mut shifted = config.clone();
+        shifted.terrain.heightmap.as_mut().unwrap().origin = Some([0.0, 0.0]);
+        shifted.swarm.deploy_position = space::Vector::new(150.0, 300.0, 100.0);
+        let corner = Simulator::new(shifted).unwrap().get_goal_surface().parametric_eval(1.0, 1.0);
+        assert_eq!((corner.x, corner.z), (300.0, 200.0));
+
+        config.swarm.deploy_position = space::Vector::new(400.0, 300.0, 0.0);
+        assert!(config.validate().is_ok());
+        assert_eq!(Simulator::new(config).unwrap_err(), Error::invalid_config("swarm.deploy_position", "must be inside the terrain boundary"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
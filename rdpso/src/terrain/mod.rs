@@ -0,0 +1,480 @@
+use std::convert::TryFrom;
+
+use crate::wasm_bindgen;
+use serde::{Serialize, Deserialize};
+
+use crate::space::{Pair, Vector};
+use super::space::Mapper;
+use super::space::{PiecewieseInterpolator, InterpolationMode};
+use crate::utils::{PerlinNoise, Rng};
+use crate::space::{Boundary, Grid, GridInterpolation, FillPolicy};
+use crate::error::{Error, Result};
+
+/// MAX_CACHE_RESOLUTION bounds Config::cache_resolution, the cache holds the square of it in samples
+pub const MAX_CACHE_RESOLUTION: usize = 4096;
+
+mod profile;
+pub use profile::{ElevationPreset, ElevationProfile};
+
+mod cache;
+pub use cache::CacheError;
+
+mod heightmap;
+pub use heightmap::HeightmapSpec;
+
+mod field;
+pub use field::HeightField;
+
+pub mod surface;
+
+/// DERIVATIVE_STEP is the distance used by the finite differences of Perlin terrains,
+/// grids use their cell size
+const DERIVATIVE_STEP: f64 = 1e-3;
+
+/// Config specifies Terrain configuarion parameters
+#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize)]
+#[wasm_bindgen]
+pub struct Config {
+    /// size represents the terrain boundary
+    pub size: usize,
+
+    /// octave_count represent the amount of times the noise function will be sampled per x, z coord
+    pub octave_count: usize,
+
+    /// octave_delta configures the sampling distance used between each different perlin sampling (octave)
+    pub octave_delta: f64,
+
+    /// scaling_factory determines how much the x and y should be scaled down before being sampled
+    pub scaling_factor: f64,
+
+    /// seed makes the terrain independent from the simulation's seed, it's drawn from the simulation otherwise
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    /// noise_octaves is the amount of octaves summed by the Perlin noise generator
+    #[serde(default = "default_noise_octaves")]
+    pub noise_octaves: usize,
+
+    /// persistence is the amplitude ratio between consecutive noise octaves
+    #[serde(default = "default_persistence")]
+    pub persistence: f64,
+
+    /// lacunarity is the frequency ratio between consecutive noise octaves
+    #[serde(default = "default_lacunarity")]
+    pub lacunarity: f64,
+
+    /// heightmap replaces the Perlin terrain by a digital elevation model,
+    /// the terrain's boundary becomes the heightmap's extent and size is ignored
+    #[wasm_bindgen(skip)]
+    #[serde(default)]
+    pub heightmap: Option<HeightmapSpec>,
+
+    /// elevation maps the Perlin noise to heights, see ElevationProfile
+    #[wasm_bindgen(skip)]
+    #[serde(default)]
+    pub elevation: ElevationProfile,
+
+    /// elevation_interpolation selects the curve between the elevation breakpoints
+    #[serde(default)]
+    pub elevation_interpolation: InterpolationMode,
+
+    /// cache_resolution bakes the Perlin terrain into a grid of that many samples per side,
+    /// which is sampled bilinearly instead of evaluating the noise. 0 disables the cache,
+    /// it is at most MAX_CACHE_RESOLUTION.
+    #[serde(default)]
+    pub cache_resolution: usize,
+}
+
+fn default_noise_octaves() -> usize { 4 }
+fn default_persistence() -> f64 { 0.5 }
+fn default_lacunarity() -> f64 { 2.0 }
+
+#[wasm_bindgen]
+impl Config {
+    pub fn new(size: usize, octave_count: usize, octave_delta: f64, scaling_factor: f64) -> Self{
+        Self {
+            size,
+            scaling_factor,
+            octave_count,
+            octave_delta,
+            seed: None,
+            noise_octaves: default_noise_octaves(),
+            persistence: default_persistence(),
+            lacunarity: default_lacunarity(),
+            heightmap: None,
+            elevation: ElevationProfile::default(),
+            elevation_interpolation: InterpolationMode::default(),
+            cache_resolution: 0,
+        }
+
+    }
+
+    /// with_noise configures the Perlin noise generator, a missing seed draws the terrain from the simulation's seed
+    pub fn with_noise(mut self, seed: Option<u64>, noise_octaves: usize, persistence: f64, lacunarity: f64) -> Self {
+        self.seed = seed;
+        self.noise_octaves = noise_octaves;
+        self.persistence = persistence;
+        self.lacunarity = lacunarity;
+        self
+    }
+
+    /// new_from_size returns with some predefined defaults
+    pub fn new_from_size(size: usize) -> Self {
+        let octaves = 4;
+        let octave_delta = 0.1;
+        Self::new(size, octaves, octave_delta, 0.01)
+    }
+
+    /// with_elevation_preset sets the elevation profile to a preset, with the given interpolation
+    pub fn with_elevation_preset(mut self, preset: ElevationPreset, interpolation: InterpolationMode) -> Self {
+        self.elevation = ElevationProfile::Preset(preset);
+        self.elevation_interpolation = interpolation;
+        self
+    }
+
+    /// with_elevation_breakpoints sets the elevation profile to the (noise[i], heights[i]) breakpoints
+    pub fn with_elevation_breakpoints(mut self, noise: Vec<f64>, heights: Vec<f64>, interpolation: InterpolationMode) -> Self {
+        self.elevation = ElevationProfile::Breakpoints(noise.into_iter().zip(heights).map(|(n, h)| [n, h]).collect());
+        self.elevation_interpolation = interpolation;
+        self
+    }
+
+    /// get_boundary returns the terrain's area, a square of side size centered at the origin.
+    /// Heightmaps span their own area, see Terrain::get_boundary.
+    pub fn get_boundary(&self) -> Boundary {
+        let offset = (self.size/2) as f64;
+        Boundary::new(-offset, offset, -offset, offset)
+    }
+
+}
+
+/// Terrain is a parametric land generator using Perlin noise, or a heightmap
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+#[repr(C)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "TerrainState")]
+pub struct Terrain {
+    config: Config,
+
+    /// noise generates Perlin terrains, heightmap terrains don't have one
+    noise: Option<PerlinNoise>,
+    /// parametric_mapper maps parametric coordinates into the world size range,
+    /// along x and z respectively
+    parametric_mapper: [Mapper; 2],
+    sampling_mapper: Mapper,
+    interpolator: PiecewieseInterpolator,
+    boundary: Boundary,
+
+    /// heightmap holds the elevation samples of heightmap terrains
+    #[serde(default)]
+    heightmap: Option<Grid>,
+
+    /// cache holds the baked heights, see Config::cache_resolution.
+    /// It isn't serialized, deserialized terrains bake it again.
+    #[serde(skip)]
+    cache: Option<Grid>,
+}
+
+/// TerrainState is the serialized form of a Terrain, which lacks the cache
+#[derive(Deserialize)]
+struct TerrainState {
+    config: Config,
+    noise: Option<PerlinNoise>,
+    parametric_mapper: [Mapper; 2],
+    sampling_mapper: Mapper,
+    interpolator: PiecewieseInterpolator,
+    boundary: Boundary,
+    #[serde(default)]
+    heightmap: Option<Grid>,
+}
+
+impl TryFrom<TerrainState> for Terrain {
+    type Error = Error;
+
+    fn try_from(state: TerrainState) -> Result<Self> {
+        if state.noise.is_none() && state.heightmap.is_none() {
+            return Err(Error::Parse("terrains need either a noise generator or a heightmap".to_string()));
+        }
+        let mut terrain = Self {
+            config: state.config,
+            noise: state.noise,
+            parametric_mapper: state.parametric_mapper,
+            sampling_mapper: state.sampling_mapper,
+            interpolator: state.interpolator,
+            boundary: state.boundary,
+            heightmap: state.heightmap,
+            cache: None,
+        };
+        terrain.bake_cache()?;
+        Ok(terrain)
+    }
+}
+
+#[wasm_bindgen]
+impl Terrain {
+    pub fn get_height(&self, x: f64, z: f64) -> f64 {
+        match self.cache.as_ref() {
+            Some(cache) => cache.sample(x, z, GridInterpolation::Bilinear, FillPolicy::Clamp),
+            None => self.get_analytic_height(x, z),
+        }
+    }
+
+    /// get_analytic_height evaluates the terrain's source, bypassing the cache
+    pub fn get_analytic_height(&self, x: f64, z: f64) -> f64 {
+        if let Some(heightmap) = self.heightmap.as_ref() {
+            return heightmap.sample(x, z, GridInterpolation::Bilinear, FillPolicy::Clamp);
+        }
+
+        let scaling = self.config.scaling_factor;
+        let x = self.sampling_mapper.map(x) * scaling;
+        let z = self.sampling_mapper.map(z) * scaling;
+
+        let noise = self.get_noise();
+        let mut sample_y = self.config.octave_delta;
+        let mut y = 0.0;
+        for _ in 0..self.config.octave_count {
+            y += noise.get3d([x, sample_y, z]);
+            sample_y += self.config.octave_delta;
+        }
+
+        y /= self.config.octave_count as f64;
+
+        self.interpolator.map_clamped(y)
+    }
+
+    /// get_normal returns the upward unit normal at (x, z)
+    pub fn get_normal(&self, x: f64, z: f64) -> Vector {
+        let [dx, dz] = self.get_gradient(x, z);
+        Vector::new(-dx, 1.0, -dz).unit()
+    }
+
+    /// get_slope returns the inclination at (x, z) along the steepest direction, in degrees
+    pub fn get_slope(&self, x: f64, z: f64) -> f64 {
+        let [dx, dz] = self.get_gradient(x, z);
+        libm::atan(libm::hypot(dx, dz)).to_degrees()
+    }
+
+    /// get_curvature returns the mean curvature at (x, z), in 1/distance units,
+    /// it's positive in hollows and negative on crests
+    pub fn get_curvature(&self, x: f64, z: f64) -> f64 {
+        let step = self.get_derivative_step();
+        let [dx, dz] = self.get_gradient(x, z);
+
+        // second derivatives by central differences of the gradient
+        let ([dx_px, dz_px], [dx_mx, dz_mx]) = (self.get_gradient(x + step, z), self.get_gradient(x - step, z));
+        let ([dx_pz, dz_pz], [dx_mz, dz_mz]) = (self.get_gradient(x, z + step), self.get_gradient(x, z - step));
+        let dxx = (dx_px - dx_mx) / (2.0 * step);
+        let dzz = (dz_pz - dz_mz) / (2.0 * step);
+        let dxz = ((dz_px - dz_mx) + (dx_pz - dx_mz)) / (4.0 * step);
+
+        let numerator = (1.0 + dz * dz) * dxx - 2.0 * dx * dz * dxz + (1.0 + dx * dx) * dzz;
+        numerator / (2.0 * libm::pow(1.0 + dx * dx + dz * dz, 1.5))
+    }
+
+    pub fn get_point_from_parametric(&self, x: f64, z: f64) -> Vector {
+        let x = self.parametric_mapper[0].map(x);
+        let z = self.parametric_mapper[1].map(z);
+        let y = self.get_height(x, z);
+
+        Vector{
+            x,
+            y,
+            z,
+        }
+    }
+
+    pub fn get_boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    /// get_size returns the side of the terrain, heightmaps return their longest side
+    pub fn get_size(&self) -> usize {
+        match self.heightmap {
+            Some(_) => {
+                let boundary = self.boundary;
+                (boundary.get_max_x() - boundary.get_min_x()).max(boundary.get_max_z() - boundary.get_min_z()).ceil() as usize
+            },
+            None => self.config.size,
+        }
+    }
+
+    pub fn is_heightmap(&self) -> bool {
+        self.heightmap.is_some()
+    }
+
+    pub fn is_cached(&self) -> bool {
+        self.cache.is_some()
+    }
+
+    /// compare_cache measures the error of the cached heights against the analytic ones
+    /// on samples x samples points, terrains without cache have no error
+    pub fn compare_cache(&self, samples: usize) -> CacheError {
+        match self.cache.as_ref() {
+            Some(grid) => cache::compare(grid, |x, z| self.get_analytic_height(x, z), samples),
+            None => CacheError::default(),
+        }
+    }
+
+    pub fn get_config(&self) -> Config {self.config.clone()}
+}
+
+impl Config {
+
+    /// elevation_interpolator builds the interpolator mapping the noise to heights
+    pub fn elevation_interpolator(&self) -> Result<PiecewieseInterpolator> {
+        let (noise, heights) = self.elevation.breakpoints().into_iter().map(|[n, h]| (n, h)).unzip();
+        PiecewieseInterpolator::new_with_mode(noise, heights, self.elevation_interpolation)
+            .map_err(|err| Error::invalid_config("terrain.elevation", &err.to_string()))
+    }
+}
+
+impl Terrain {
+
+    /// get_gradient returns the height's partial derivatives [dh/dx, dh/dz] at (x, z).
+    /// Perlin terrains are differentiated analytically, grids by central differences over a cell.
+    pub fn get_gradient(&self, x: f64, z: f64) -> Pair {
+        if self.cache.is_some() || self.heightmap.is_some() {
+            let step = self.get_derivative_step();
+            return [
+                (self.get_height(x + step, z) - self.get_height(x - step, z)) / (2.0 * step),
+                (self.get_height(x, z + step) - self.get_height(x, z - step)) / (2.0 * step),
+            ];
+        }
+
+        // chain rule through get_analytic_height: sampling, octaves average and elevation profile
+        let scaling = self.config.scaling_factor;
+        let ratio = self.sampling_mapper.get_ratio() * scaling;
+        let x = self.sampling_mapper.map(x) * scaling;
+        let z = self.sampling_mapper.map(z) * scaling;
+
+        let noise = self.get_noise();
+        let mut sample_y = self.config.octave_delta;
+        let (mut y, mut dx, mut dz) = (0.0, 0.0, 0.0);
+        for _ in 0..self.config.octave_count {
+            let (value, gradient) = noise.get3d_gradient([x, sample_y, z]);
+            y += value;
+            dx += gradient[0];
+            dz += gradient[2];
+            sample_y += self.config.octave_delta;
+        }
+
+        let count = self.config.octave_count as f64;
+        let slope = self.interpolator.derivative_clamped(y / count) * ratio / count;
+        [slope * dx, slope * dz]
+    }
+
+    /// get_noise returns the Perlin generator, only heightmap terrains lack one
+    /// and they never sample the noise
+    fn get_noise(&self) -> &PerlinNoise {
+        match self.noise.as_ref() {
+            Some(noise) => noise,
+            None => unreachable!("terrains without noise have a heightmap"),
+        }
+    }
+
+    /// get_derivative_step returns the finite differences' distance, the cell size of grids
+    fn get_derivative_step(&self) -> f64 {
+        match self.cache.as_ref().or(self.heightmap.as_ref()) {
+            Some(grid) => grid.get_cell_size(),
+            None => DERIVATIVE_STEP,
+        }
+    }
+
+    pub fn new(config: Config, rng: &mut Rng) -> Result<Self> {
+        let interpolator = config.elevation_interpolator()?;
+
+        // heightmaps replace the noise, which isn't generated then
+        let heightmap = config.heightmap.as_ref().map(HeightmapSpec::load).transpose()?;
+        let noise = match (config.seed, heightmap.is_some()) {
+            (_, true) => None,
+            (Some(seed), false) => Some(PerlinNoise::new_seeded(seed, config.noise_octaves, config.persistence, config.lacunarity)),
+            (None, false) => Some(PerlinNoise::from_rng(rng, config.noise_octaves, config.persistence, config.lacunarity)),
+        };
+
+        let boundary = heightmap.as_ref().map_or_else(|| config.get_boundary(), Grid::get_boundary);
+
+        let offset = (config.size/2) as f64;
+        let parametric_mapper = [
+            Mapper::new_from_pair([0.0, 1.0], [boundary.get_min_x(), boundary.get_max_x()]),
+            Mapper::new_from_pair([0.0, 1.0], [boundary.get_min_z(), boundary.get_max_z()]),
+        ];
+        let sampling_mapper = Mapper::new_from_pair([-offset, offset], [0.0, config.size as f64]);
+        let mut terrain = Self {
+            config,
+            noise,
+            parametric_mapper,
+            interpolator,
+            boundary,
+            sampling_mapper,
+            heightmap,
+            cache: None,
+        };
+        terrain.bake_cache()?;
+        Ok(terrain)
+    }
+
+    /// bake_cache samples the terrain as set by Config::cache_resolution,
+    /// heightmaps are grids already
+    fn bake_cache(&mut self) -> Result<()> {
+        let resolution = self.config.cache_resolution;
+        if resolution > 0 && self.heightmap.is_none() {
+            self.cache = Some(cache::bake(|x, z| self.get_analytic_height(x, z), self.boundary, resolution)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_terrains_ignore_the_simulation_seed() {
+        let seeded = Config::new_from_size(1000).with_noise(Some(5), 6, 0.45, 2.2);
+        let a = Terrain::new(seeded.clone(), &mut Rng::new(1)).unwrap();
+        let b = Terrain::new(seeded, &mut Rng::new(2)).unwrap();
+        let unseeded = Terrain::new(Config::new_from_size(1000), &mut Rng::new(1)).unwrap();
+        assert_eq!(a.get_height(120.0, -40.0), b.get_height(120.0, -40.0));
+        assert_ne!(a.get_height(120.0, -40.0), unseeded.get_height(120.0, -40.0));
+    }
+
+    #[test]
+    fn terrain_geometry_matches_its_heights() {
+        let config = Config::new_from_size(1000)
+            .with_noise(Some(3), 4, 0.5, 2.0)
+            .with_elevation_preset(ElevationPreset::Hills, InterpolationMode::CatmullRom);
+        let analytic = Terrain::new(config.clone(), &mut Rng::new(0)).unwrap();
+        let cached = Terrain::new(Config { cache_resolution: 501, ..config }, &mut Rng::new(0)).unwrap();
+
+        let points: Vec<(f64, f64)> = (0..25).map(|i| (-400.0 + 200.0 * (i % 5) as f64, -390.0 + 190.0 * (i / 5) as f64)).collect();
+        let step = 1e-3;
+        let (mut highest, mut lowest) = (points[0], points[0]);
+        for &(x, z) in points.iter() {
+            // the analytic gradient agrees with the heights' central differences
+            let [dx, dz] = analytic.get_gradient(x, z);
+            let expected_dx = (analytic.get_height(x + step, z) - analytic.get_height(x - step, z)) / (2.0 * step);
+            let expected_dz = (analytic.get_height(x, z + step) - analytic.get_height(x, z - step)) / (2.0 * step);
+            assert!((dx - expected_dx).abs() < 1e-4 && (dz - expected_dz).abs() < 1e-4, "{} {} {} {}", dx, expected_dx, dz, expected_dz);
+
+            // the slope is the normal's tilt from the vertical
+            let normal = analytic.get_normal(x, z);
+            assert!((normal.magnitude() - 1.0).abs() < 1e-9);
+            assert!((libm::acos(normal.y).to_degrees() - analytic.get_slope(x, z)).abs() < 1e-6);
+
+            // the cached terrain is differentiated over its cells, its normals stay within a few degrees
+            let difference = (normal - cached.get_normal(x, z)).magnitude();
+            assert!(difference < 0.15, "{} at {:?}", difference, (x, z));
+
+            if analytic.get_height(x, z) > analytic.get_height(highest.0, highest.1) {
+                highest = (x, z);
+            }
+            if analytic.get_height(x, z) < analytic.get_height(lowest.0, lowest.1) {
+                lowest = (x, z);
+            }
+        }
+        assert!(analytic.get_curvature(highest.0, highest.1) < 0.0);
+        assert!(analytic.get_curvature(lowest.0, lowest.1) > 0.0);
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Serialize, Deserialize};
+
+use crate::wasm_bindgen;
+use crate::space::Pair;
+
+/// ElevationPreset enumerates ready made elevation profiles
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+pub enum ElevationPreset {
+    /// Mountains are flat plains with tall mountains, the historical terrain
+    #[default]
+    Mountains = 0,
+
+    /// Hills roll gently over the whole map
+    Hills = 1,
+
+    /// Canyon is a high plain cut by deep, steep sided valleys
+    Canyon = 2,
+
+    /// Plateau is a low plain with steep sided mesas
+    Plateau = 3,
+}
+
+impl ElevationPreset {
+
+    /// breakpoints returns the preset's (noise, height) breakpoints
+    pub fn breakpoints(&self) -> Vec<Pair> {
+        match self {
+            Self::Mountains => vec![[0.0, 0.0], [0.25, 0.0], [0.5, 30.0], [1.0, 500.0]],
+            Self::Hills => vec![[0.0, 0.0], [0.3, 10.0], [0.5, 60.0], [0.7, 120.0], [1.0, 180.0]],
+            Self::Canyon => vec![[0.0, 0.0], [0.3, 5.0], [0.38, 160.0], [1.0, 200.0]],
+            Self::Plateau => vec![[0.0, 0.0], [0.45, 20.0], [0.55, 180.0], [1.0, 200.0]],
+        }
+    }
+}
+
+/// ElevationProfile maps the Perlin noise, roughly in [0, 1], to heights.
+/// In configs it's either a preset, ie `elevation = "Hills"`,
+/// or a list of (noise, height) breakpoints, ie `elevation = [[0.0, 0.0], [0.5, 50.0], [1.0, 300.0]]`.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ElevationProfile {
+    Preset(ElevationPreset),
+    Breakpoints(Vec<Pair>),
+}
+
+impl Default for ElevationProfile {
+    fn default() -> Self {
+        Self::Preset(ElevationPreset::default())
+    }
+}
+
+impl ElevationProfile {
+
+    /// breakpoints returns the profile's (noise, height) breakpoints
+    pub fn breakpoints(&self) -> Vec<Pair> {
+        match self {
+            Self::Preset(preset) => preset.breakpoints(),
+            Self::Breakpoints(breakpoints) => breakpoints.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::tests::config;
+    use crate::Simulator;
+
+    #[test]
+    fn elevation_profiles_shape_the_terrain() {
+        let build = |elevation: serde_json::Value, interpolation: &str| {
+            let mut config = config(2);
+            let mut terrain = serde_json::to_value(&config.terrain).unwrap();
+            terrain["elevation"] = elevation;
+            terrain["elevation_interpolation"] = serde_json::json!(interpolation);
+            config.terrain = serde_json::from_value(terrain).unwrap();
+            Simulator::new(config)
+        };
+
+        let mountains = Simulator::new(config(2)).unwrap();
+        let custom = build(serde_json::json!([[0.0, 0.0], [0.25, 0.0], [0.5, 30.0], [1.0, 500.0]]), "Linear").unwrap();
+        let canyon = build(serde_json::json!("Canyon"), "MonotoneCubic").unwrap();
+        let (x, z) = (130.0, -70.0);
+        assert_eq!(custom.terrain.get_height(x, z), mountains.terrain.get_height(x, z));
+        assert_ne!(canyon.terrain.get_height(x, z), mountains.terrain.get_height(x, z));
+
+        let restored = Simulator::restore_bytes(&canyon.snapshot_bytes().unwrap()).unwrap();
+        assert_eq!(restored.config.terrain.elevation, ElevationProfile::Preset(ElevationPreset::Canyon));
+        assert_eq!(restored.terrain.get_height(x, z), canyon.terrain.get_height(x, z));
+
+        let unsorted = build(serde_json::json!([[0.5, 0.0], [0.25, 10.0]]), "CatmullRom").unwrap_err();
+        assert!(matches!(unsorted, Error::InvalidConfig { field, .. } if field == "terrain.elevation"));
+    }
+}
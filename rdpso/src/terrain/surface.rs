@@ -0,0 +1,190 @@
+//! surface holds analytic height fields, whose exact shape makes them handy test terrains
+
+use crate::space::{Boundary, Vector};
+use super::HeightField;
+
+/// Plane is a flat ground at a constant height
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Plane {
+    pub height: f64,
+    pub boundary: Boundary,
+}
+
+impl Plane {
+    pub fn new(height: f64, boundary: Boundary) -> Self {
+        Self {
+            height,
+            boundary,
+        }
+    }
+}
+
+impl HeightField for Plane {
+    fn get_height(&self, _x: f64, _z: f64) -> f64 {
+        self.height
+    }
+
+    fn get_boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    fn get_normal(&self, _x: f64, _z: f64) -> Vector {
+        Vector::unit_y()
+    }
+}
+
+/// Cone is a single peak rising linearly from base to peak over radius, centered at (x, z)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cone {
+    pub x: f64,
+    pub z: f64,
+    pub base: f64,
+    pub peak: f64,
+    pub radius: f64,
+    pub boundary: Boundary,
+}
+
+impl Cone {
+    pub fn new(x: f64, z: f64, base: f64, peak: f64, radius: f64, boundary: Boundary) -> Self {
+        Self {
+            x,
+            z,
+            base,
+            peak,
+            radius,
+            boundary,
+        }
+    }
+}
+
+impl HeightField for Cone {
+    fn get_height(&self, x: f64, z: f64) -> f64 {
+        let distance = libm::hypot(x - self.x, z - self.z);
+        self.base + (self.peak - self.base) * (1.0 - distance / self.radius).max(0.0)
+    }
+
+    fn get_boundary(&self) -> Boundary {
+        self.boundary
+    }
+}
+
+/// Ridge is a straight crest through (x, z), running along angle radians from the x axis.
+/// The height falls linearly from peak on the crest to base at half_width from it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ridge {
+    pub x: f64,
+    pub z: f64,
+    pub angle: f64,
+    pub base: f64,
+    pub peak: f64,
+    pub half_width: f64,
+    pub boundary: Boundary,
+}
+
+impl Ridge {
+    pub fn new(x: f64, z: f64, angle: f64, base: f64, peak: f64, half_width: f64, boundary: Boundary) -> Self {
+        Self {
+            x,
+            z,
+            angle,
+            base,
+            peak,
+            half_width,
+            boundary,
+        }
+    }
+}
+
+impl HeightField for Ridge {
+    fn get_height(&self, x: f64, z: f64) -> f64 {
+        let (sin, cos) = (libm::sin(self.angle), libm::cos(self.angle));
+        let distance = ((z - self.z) * cos - (x - self.x) * sin).abs();
+        self.base + (self.peak - self.base) * (1.0 - distance / self.half_width).max(0.0)
+    }
+
+    fn get_boundary(&self) -> Boundary {
+        self.boundary
+    }
+}
+
+/// Blend selects how the parts of a composite are combined
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Blend {
+    /// Max keeps the highest part, ie separate hills on a plane
+    #[default]
+    Max,
+
+    /// Sum adds the parts up, ie a bump on top of a slope
+    Sum,
+}
+
+/// Composite combines several height fields over a common boundary
+#[derive(Debug)]
+pub struct Composite {
+    boundary: Boundary,
+    blend: Blend,
+    parts: Vec<Box<dyn HeightField>>,
+}
+
+impl Composite {
+    pub fn new(boundary: Boundary, blend: Blend) -> Self {
+        Self {
+            boundary,
+            blend,
+            parts: Vec::new(),
+        }
+    }
+
+    /// with adds a part to the composite
+    pub fn with(mut self, part: impl HeightField + 'static) -> Self {
+        self.parts.push(Box::new(part));
+        self
+    }
+
+    pub fn get_parts(&self) -> &[Box<dyn HeightField>] {
+        &self.parts
+    }
+}
+
+impl HeightField for Composite {
+    /// get_height is 0 for composites without parts
+    fn get_height(&self, x: f64, z: f64) -> f64 {
+        let heights = self.parts.iter().map(|part| part.get_height(x, z));
+        match self.blend {
+            Blend::Max => heights.reduce(f64::max).unwrap_or(0.0),
+            Blend::Sum => heights.sum(),
+        }
+    }
+
+    fn get_boundary(&self) -> Boundary {
+        self.boundary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surfaces_have_their_analytic_shape() {
+        let boundary = Boundary::new(-100.0, 100.0, -100.0, 100.0);
+        let cone = Cone::new(20.0, 0.0, 0.0, 50.0, 50.0, boundary);
+        assert_eq!(cone.get_height(20.0, 0.0), 50.0);
+        assert_eq!(cone.get_height(45.0, 0.0), 25.0);
+        assert_eq!(cone.get_height(-50.0, 0.0), 0.0);
+
+        // the flank's normal leans away from the peak by the slope
+        let normal = cone.get_normal(45.0, 0.0);
+        let expected = Vector::new(1.0, 1.0, 0.0).unit();
+        assert!((normal - expected).magnitude() < 1e-6, "{:?}", normal);
+
+        let ridge = Ridge::new(0.0, 0.0, 0.0, 10.0, 30.0, 20.0, boundary);
+        assert_eq!(ridge.get_height(80.0, 0.0), 30.0);
+        assert_eq!(ridge.get_height(-40.0, 10.0), 20.0);
+
+        let hills = Composite::new(boundary, Blend::Max).with(Plane::new(5.0, boundary)).with(cone).with(ridge);
+        assert_eq!(hills.get_height(0.0, -90.0), 10.0);
+        assert_eq!(hills.get_height(20.0, 0.0), 50.0);
+        assert_eq!(hills.get_point_from_parametric(0.5, 1.0), Vector::new(0.0, 10.0, 100.0));
+    }
+}
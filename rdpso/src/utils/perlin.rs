@@ -1,32 +1,55 @@
 // PerlinNoise gen taken from https://github.com/processing-js/processing-js/blob/master/src/P5Functions/Math.js
 
-use crate::utils;
+use crate::utils::Rng;
+use serde::{Serialize, Deserialize};
 
 use wasm_bindgen::prelude::*;
 
+fn default_lacunarity() -> f64 {
+    2.0
+}
+
 /// Perlin Noise generator that outputs 1/2/3D Perlin noise
 #[derive(Clone, Debug)]
 #[wasm_bindgen]
+#[derive(Serialize, Deserialize)]
 pub struct PerlinNoise {
-    perm: [usize; 512],
+    /// perm is the permutation table, its 256 entries are repeated to avoid wrapping indexes
+    perm: Vec<usize>,
     octaves: usize,
-    fallout: f64,
+
+    /// persistence is the amplitude ratio between consecutive octaves
+    #[serde(alias = "fallout")]
+    persistence: f64,
+
+    /// lacunarity is the frequency ratio between consecutive octaves
+    #[serde(default = "default_lacunarity")]
+    lacunarity: f64,
 }
 
 impl PerlinNoise {
-    pub fn new() -> PerlinNoise {
-        let mut perm = [0; 512];
+    /// new shuffles the permutation table with rng, using 4 octaves of persistence 0.5 and lacunarity 2
+    pub fn new(rng: &mut Rng) -> PerlinNoise {
+        Self::from_rng(rng, 4, 0.5, default_lacunarity())
+    }
 
-        for i in 0..256 {
-            perm[i] = i;
+    /// new_seeded builds a generator which only depends on its arguments,
+    /// the same seed always produces the same noise
+    pub fn new_seeded(seed: u64, octaves: usize, persistence: f64, lacunarity: f64) -> PerlinNoise {
+        Self::from_rng(&mut Rng::new(seed), octaves, persistence, lacunarity)
+    }
+
+    /// from_rng shuffles the permutation table with rng
+    pub fn from_rng(rng: &mut Rng, octaves: usize, persistence: f64, lacunarity: f64) -> PerlinNoise {
+        let mut perm = vec![0; 512];
+
+        for (i, p) in perm.iter_mut().enumerate().take(256) {
+            *p = i;
         }
 
         for i in 0..256 {
-            let j = utils::gen_random_range(0, 256) & 0xFF;
-            let t = perm[j];
-
-            perm[j] = perm[i];
-            perm[i] = t;
+            let j = rng.gen_random_range(0, 256) & 0xFF;
+            perm.swap(j, i);
         }
 
         for i in 0..256 {
@@ -35,8 +58,9 @@ impl PerlinNoise {
 
         PerlinNoise {
             perm,
-            octaves: 4,
-            fallout: 0.5,
+            octaves,
+            persistence,
+            lacunarity,
         }
     }
 
@@ -47,13 +71,34 @@ impl PerlinNoise {
         let mut sum = 0.0;
 
         for _ in 0..self.octaves {
-            effect *= self.fallout;
+            effect *= self.persistence;
             sum += effect * (1.0 + self.noise3d(k * args[0], k * args[1], k * args[2])) / 2.0;
-            k *= 2.0
+            k *= self.lacunarity;
         }
 
         sum
     }
+
+    /// get3d_gradient returns the 3D noise along with its analytic gradient
+    pub fn get3d_gradient(&self, args: [f64; 3]) -> (f64, [f64; 3]) {
+        let mut effect = 1.0;
+        let mut k = 1.0;
+        let mut sum = 0.0;
+        let mut gradient = [0.0; 3];
+
+        for _ in 0..self.octaves {
+            effect *= self.persistence;
+            let (noise, derivatives) = self.noise3d_gradient(k * args[0], k * args[1], k * args[2]);
+            sum += effect * (1.0 + noise) / 2.0;
+            for (g, d) in gradient.iter_mut().zip(derivatives.iter()) {
+                *g += effect * k * d / 2.0;
+            }
+            k *= self.lacunarity;
+        }
+
+        (sum, gradient)
+    }
+
     /// Perlin Noise in 2D
     pub fn get2d(&self, args: [f64; 2]) -> f64 {
         let mut effect = 1.0;
@@ -61,10 +106,10 @@ impl PerlinNoise {
         let mut sum = 0.0;
 
         for _ in 0..self.octaves {
-            effect *= self.fallout;
+            effect *= self.persistence;
             sum += effect * ((1.0 + self.noise2d(k * args[0], k * args[1])) / 2.0);
 
-            k *= 2.0
+            k *= self.lacunarity;
         }
 
         sum
@@ -77,9 +122,9 @@ impl PerlinNoise {
         let mut sum = 0.0;
 
         for _ in 0..self.octaves {
-            effect *= self.fallout;
+            effect *= self.persistence;
             sum += effect * ((1.0 + self.noise1d(k * x)) / 2.0);
-            k *= 2.0
+            k *= self.lacunarity;
         }
 
         sum
@@ -136,6 +181,40 @@ impl PerlinNoise {
         )
     }
 
+    /// noise3d_gradient is noise3d written as a weighted sum of the corner contributions,
+    /// so that it can be differentiated through the fade curves
+    fn noise3d_gradient(&self, x: f64, y: f64, z: f64) -> (f64, [f64; 3]) {
+        let x0 = (x.floor() as usize) & 255;
+        let y0 = (y.floor() as usize) & 255;
+        let z0 = (z.floor() as usize) & 255;
+
+        let p = [x - x.floor(), y - y.floor(), z - z.floor()];
+        let fade = p.map(|t| (3.0 - 2.0 * t) * t * t);
+        let dfade = p.map(|t| 6.0 * t * (1.0 - t));
+
+        let mut value = 0.0;
+        let mut gradient = [0.0; 3];
+        for corner in 0..8 {
+            let c = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let hash = self.perm[self.perm[self.perm[x0 + c[0]] + y0 + c[1]] + z0 + c[2]];
+            let g = grad3d_vector(hash);
+            let offset = [p[0] - c[0] as f64, p[1] - c[1] as f64, p[2] - c[2] as f64];
+            let dot = g[0] * offset[0] + g[1] * offset[1] + g[2] * offset[2];
+
+            // the corner's weight along each axis, and its derivative
+            let w = [0, 1, 2].map(|i| if c[i] == 1 { fade[i] } else { 1.0 - fade[i] });
+            let dw = [0, 1, 2].map(|i| if c[i] == 1 { dfade[i] } else { -dfade[i] });
+            let weight = w[0] * w[1] * w[2];
+
+            value += weight * dot;
+            gradient[0] += dw[0] * w[1] * w[2] * dot + weight * g[0];
+            gradient[1] += w[0] * dw[1] * w[2] * dot + weight * g[1];
+            gradient[2] += w[0] * w[1] * dw[2] * dot + weight * g[2];
+        }
+
+        (value, gradient)
+    }
+
     fn noise2d(&self, mut x: f64, mut y: f64) -> f64 {
         let x0 = (x.floor() as usize) & 255;
         let y0 = (y.floor() as usize) & 255;
@@ -197,14 +276,21 @@ fn grad3d(hash: usize, x: f64, y: f64, z: f64) -> f64 {
     v + u
 }
 
+/// grad3d_vector returns the gradient grad3d dots with, ie the derivative of grad3d
+fn grad3d_vector(hash: usize) -> [f64; 3] {
+    [
+        grad3d(hash, 1.0, 0.0, 0.0),
+        grad3d(hash, 0.0, 1.0, 0.0),
+        grad3d(hash, 0.0, 0.0, 1.0),
+    ]
+}
+
+/// grad2d picks one of the four diagonal gradients, so that every direction is equally likely
 fn grad2d(hash: usize, x: f64, y: f64) -> f64 {
-    let v = if hash & 1 == 0 { x } else { y };
+    let u = if hash & 1 == 0 { x } else { -x };
+    let v = if hash & 2 == 0 { y } else { -y };
 
-    if (hash & 1) == 0 {
-        -v
-    } else {
-        v
-    }
+    u + v
 }
 
 fn grad1d(hash: usize, x: f64) -> f64 {
@@ -221,4 +307,40 @@ fn lerp(t: f64, a: f64, b: f64) -> f64 {
 }
 // Fade function as defined by Ken Perlin.  This eases coordinate values
 // so that they will "ease" towards integral values.  This ends up smoothing
-// the final output.
\ No newline at end of file
+// the final output.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_noise_is_reproducible_and_unbiased() {
+        let a = PerlinNoise::new_seeded(42, 6, 0.6, 2.5);
+        let b = PerlinNoise::new_seeded(42, 6, 0.6, 2.5);
+        let other = PerlinNoise::new_seeded(43, 6, 0.6, 2.5);
+        assert_eq!(a.get3d([1.3, 0.2, 4.7]), b.get3d([1.3, 0.2, 4.7]));
+        assert_ne!(a.get3d([1.3, 0.2, 4.7]), other.get3d([1.3, 0.2, 4.7]));
+
+        // the gradients cancel out along both axes
+        let (sx, sy) = (0..4).fold((0.0, 0.0), |(sx, sy), hash| (sx + grad2d(hash, 1.0, 0.0), sy + grad2d(hash, 0.0, 1.0)));
+        assert_eq!((sx, sy), (0.0, 0.0));
+
+        let samples = 10_000;
+        let mean = (0..samples)
+            .map(|i| a.noise2d(0.37 * i as f64, 0.61 * (i / 100) as f64))
+            .sum::<f64>() / samples as f64;
+        assert!(mean.abs() < 0.02, "{}", mean);
+
+        // the analytic gradient matches the noise, and its central differences
+        let (point, step) = ([1.3, 0.2, 4.7], 1e-6);
+        let (value, gradient) = a.get3d_gradient(point);
+        assert!((value - a.get3d(point)).abs() < 1e-12);
+        for (axis, derivative) in gradient.iter().enumerate() {
+            let (mut above, mut below) = (point, point);
+            above[axis] += step;
+            below[axis] -= step;
+            let expected = (a.get3d(above) - a.get3d(below)) / (2.0 * step);
+            assert!((derivative - expected).abs() < 1e-5, "{} {} {}", axis, derivative, expected);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use serde::{Serialize, Deserialize};
+
+/// Rng is a small seedable pseudo random number generator (xoshiro256**).
+///
+/// It is implemented here instead of relying on the platform's generator
+/// so that a seed produces the very same sequence on native and on wasm.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+
+    /// new seeds the generator, the state is expanded from the seed with splitmix64
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut state = [0; 4];
+        for s in state.iter_mut() {
+            x = x.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            *s = z ^ (z >> 31);
+        }
+        Self { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    /// gen_random generates a random float in the interval [0, 1)
+    pub fn gen_random(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// gen_gaussian generates a standard normal sample with the Box-Muller transform
+    pub fn gen_gaussian(&mut self) -> f64 {
+        let u = 1.0 - self.gen_random();
+        let v = self.gen_random();
+        (-2.0 * libm::log(u)).sqrt() * libm::cos(std::f64::consts::TAU * v)
+    }
+
+    /// gen_random_range generates a random integer in the interval [min, max)
+    pub fn gen_random_range(&mut self, min: usize, max: usize) -> usize {
+        let delta = (max - min) as f64;
+        min + (delta * self.gen_random()) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+
+        let mut rng = Rng::new(7);
+        for _ in 0..1000 {
+            let x = rng.gen_random_range(10, 20);
+            assert!((10..20).contains(&x));
+        }
+    }
+}
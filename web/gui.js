@@ -1,10 +1,16 @@
 import { GUI } from 'three/addons/libs/lil-gui.module.min.js';
 import {draw_terrain, draw_goal_surface} from "./surface.js";
 import {draw_particles} from "./swarm.js";
+import {GoalInfo, GoalTransform, Benchmark} from "rdpso-sim";
 
 
 export function build_menu(sim, scene, meshes) {
 
+    // goals maps the display name of every benchmark to its config name
+    const infos = GoalInfo.all();
+    const goals = Object.fromEntries(infos.map((info) => [info.get_name(), Benchmark[info.benchmark]]));
+    const current = JSON.parse(sim.dump_config()).ctx.goal;
+
     const menu = {
         Reset: () => {
             scene.clear();
@@ -28,10 +34,12 @@ export function build_menu(sim, scene, meshes) {
                 sim.set_config(config);
                 document.querySelector("#config-result").textContent = "";
             } catch (error) {
+                // parse errors point at the offending field, ie the column of an invalid goal expression
                 console.log(error)
-                document.querySelector("#config-result").textContent = error;
+                document.querySelector("#config-result").textContent = error.message ?? error;
             }
         },
+        Function: current,
         Goal: true,
         Terrain: true,
     };
@@ -39,6 +47,22 @@ export function build_menu(sim, scene, meshes) {
 
     gui.add(menu, "Reset");
     gui.add(menu, "Apply");
+    gui.add(menu, "Function", goals).onChange((goal) => {
+        const config = JSON.parse(sim.dump_config());
+        const info = infos.find((info) => Benchmark[info.benchmark] === goal);
+        // stretch the goal's canonical domain over the whole terrain
+        const transform = GoalTransform.fit(info.domain, sim.get_terrain_boundary());
+        config.ctx.goal = goal;
+        config.ctx.transform = {
+            scale: transform.scale,
+            offset_x: transform.offset_x,
+            offset_z: transform.offset_z,
+            rotation: transform.rotation,
+        };
+        document.querySelector("#config").value = JSON.stringify(config, null, 2);
+        menu.Apply();
+        menu.Reset();
+    });
     gui.add(menu, "Goal").onChange(() => meshes.goal.visible = !meshes.goal.visible);
     gui.add(menu, "Terrain").onChange(() => meshes.terrain.visible = !meshes.terrain.visible);
 
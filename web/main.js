@@ -4,6 +4,8 @@ import {draw_terrain, draw_goal_surface} from "./surface.js";
 import {step, draw_particles} from "./swarm.js";
 import {build_menu} from "./gui.js";
 
+const GOAL_REDRAW_INTERVAL = 10;
+
 buildSimulator().then( (sim) => {
     document.querySelector("#config").value = sim.dump_config();
 
@@ -15,9 +17,19 @@ buildSimulator().then( (sim) => {
 
     build_menu(sim, scene, meshes);
 
+    let frame = 0;
     function animate() {
         step(sim, meshes.particles);
 
+        // time dependent goals change under the particles, their surface is redrawn every few frames
+        if (sim.is_goal_dynamic() && ++frame % GOAL_REDRAW_INTERVAL === 0) {
+            scene.remove(meshes.goal);
+            meshes.goal.geometry.dispose();
+            const goal = draw_goal_surface(scene, sim);
+            goal.visible = meshes.goal.visible;
+            meshes.goal = goal;
+        }
+
         redraw();
 
         setTimeout(function () {
@@ -6,14 +6,17 @@ function getConfig() {
     const c1 = 2.0;
     const c2 = 1.0;
     const c3 = 1.0;
+    const c4 = 0.5;
     const max_vel = 5.0;
-    const psoParams = sim.ParameterSet.new(w, c1, c2, c3, max_vel);
+    const psoParams = sim.ParameterSet.new(w, c1, c2, c3, c4, max_vel);
 
     const size = 1000;
     const octaves = 5;
     const octave_delta = 0.01;
     const scaling_factor = 0.012;
     const terrainConfig = sim.Config.new(size, octaves, octave_delta, scaling_factor);
+    // bake the noise once, the collision sensors query the heights many times per particle
+    terrainConfig.cache_resolution = 501;
 
     const range = 17.0;
     const linear_step_size = 0.5;
@@ -26,22 +29,47 @@ function getConfig() {
     const deployPos = sim.Vector.new(450, 75, 450);
     const deploySpread = 10.0;
     const v0 = 0.1;
-    const swarm = sim.SwarmConfig.new(swarmSize, deployPos, deploySpread, v0);
+    const commRange = 150.0;
+    const lineOfSight = true;
+    const losStepSize = 1.0;
+    const connectivityThreshold = 0.8;
+    const comm = sim.CommConfig.new(commRange, lineOfSight, losStepSize, connectivityThreshold);
+    const topology = sim.TopologyConfig.new(sim.TopologyKind.Star, 0);
+    const swarm = sim.SwarmConfig.new(swarmSize, deployPos, deploySpread, v0, comm, topology);
 
-    const ctx = sim.Ctx.new(sim.Goal.Griewank, sim.Strategy.Minimize);
+    const initialSwarms = 1;
+    const minSwarms = 1;
+    const maxSwarms = 2;
+    const minPopulation = 2;
+    const maxPopulation = 5;
+    const stagnationThreshold = 20;
+    const darwin = sim.DarwinConfig.new(initialSwarms, minSwarms, maxSwarms, minPopulation, maxPopulation, stagnationThreshold);
+
+    const ctx = sim.Ctx.new(sim.Benchmark.Griewank, sim.Strategy.Minimize);
 
     const particleLog = 20;
     const particle = sim.ParticleConfig.new(particleLog);
 
-    const config = sim.SimConfig.new(psoParams, terrainConfig, controller, swarm, ctx, particle);
+    const seed = 42n;
+
+    const config = sim.SimConfig.new(psoParams, terrainConfig, controller, swarm, darwin, ctx, particle, seed);
 
     return config;
 }
 
+// buildSimulator replays the recording given by the `recording` query parameter, if any,
+// ie ?recording=/runs/griewank.bin, otherwise it runs a live simulation
 export async function buildSimulator() {
-    return initWasm().then(() => {
-        const conf = getConfig();
-        const simulator =  sim.Simulator.new(conf);
-        return simulator;
-    })
+    await initWasm();
+
+    const recording = new URLSearchParams(window.location.search).get("recording");
+    if (recording) {
+        const response = await fetch(recording);
+        const data = new Uint8Array(await response.arrayBuffer());
+        return sim.Simulator.from_recording(data);
+    }
+
+    const conf = getConfig();
+    const simulator =  sim.Simulator.new(conf);
+    return simulator;
 }
\ No newline at end of file